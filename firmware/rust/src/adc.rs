@@ -1,5 +1,8 @@
-use crate::{impl_enum_cycle, utils::EnumCycle, Adc0, Sigrow, Vref};
-use avrxmega_hal::pac::{adc0, vref};
+#[cfg(target_arch = "avr")]
+use crate::{board::AnalogSource, Adc0, Sigrow, Vref};
+use crate::{impl_enum_cycle, utils::EnumCycle, SavedSettings, Setting};
+#[cfg(target_arch = "avr")]
+use avrxmega_hal::pac::adc0;
 
 #[derive(Clone, Copy)]
 #[repr(u8)]
@@ -14,12 +17,89 @@ pub enum AdcChannel {
     Gnd,
     Vref,
 }
-pub type Resolution = adc0::ctrla::RESSEL_A;
-pub type SampleNumber = adc0::ctrlb::SAMPNUM_A;
-pub type Prescaler = adc0::ctrlc::PRESC_A;
-pub type AdcReferenceVoltage = adc0::ctrlc::REFSEL_A;
-pub type InitDelay = adc0::ctrld::INITDLY_A;
-pub type IntReferenceVoltage = vref::ctrla::ADC0REFSEL_A;
+
+// The setting enums mirror the PAC's field variants one-to-one (same names and values),
+// so the settings can be used off-target and are transmuted into register writes.
+
+// ADC0.CTRLA.RESSEL
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Resolution {
+    _10BIT,
+    _8BIT,
+}
+
+// ADC0.CTRLB.SAMPNUM
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SampleNumber {
+    ACC1,
+    ACC2,
+    ACC4,
+    ACC8,
+    ACC16,
+    ACC32,
+    ACC64,
+}
+
+// ADC0.CTRLC.PRESC
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Prescaler {
+    DIV2,
+    DIV4,
+    DIV8,
+    DIV16,
+    DIV32,
+    DIV64,
+    DIV128,
+    DIV256,
+}
+
+// ADC0.CTRLC.REFSEL
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AdcReferenceVoltage {
+    INTREF,
+    VDDREF,
+}
+
+// ADC0.CTRLD.INITDLY
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum InitDelay {
+    DLY0,
+    DLY16,
+    DLY32,
+    DLY64,
+    DLY128,
+    DLY256,
+}
+
+// VREF.CTRLA.ADC0REFSEL
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum IntReferenceVoltage {
+    _0V55,
+    _1V1,
+    _2V5,
+    _4V34,
+    _1V5,
+}
+
+// converts a setting enum into the matching PAC variant
+#[cfg(target_arch = "avr")]
+macro_rules! pac_variant {
+    ($value:expr) => {
+        unsafe { core::mem::transmute($value as u8) }
+    };
+}
 
 // helper macro to simply inc/dec enum values for settings
 #[cfg(not(feature = "board_v0"))]
@@ -57,10 +137,16 @@ impl EnumCycle for IntReferenceVoltage {
     }
 }
 
+pub const SAMPLE_DELAY_MAX: u8 = 15;
+pub const SAMPLE_LENGTH_MAX: u8 = 31;
+// one byte per field, see AdcSettings::to_bytes
+pub const ADC_SETTINGS_SIZE: usize = 10;
+
 const VREF_E5_VALUES: [u32; 5] = [55000, 110000, 250000, 434000, 150000];
-const VREF_VDD_VALUE: u32 = 360000; // ~3.6V for Vdd assuming LIR2032 battery
+// Vdd in 10mV units until it is measured, ~3.6V for a charged LIR2032
+pub const VDD_DEFAULT: u16 = 360;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct AdcSettings {
     // ADC0.CTRLA
     pub resolution: Resolution,
@@ -100,52 +186,209 @@ impl Default for AdcSettings {
     }
 }
 
+impl AdcSettings {
+    const PRECISION_DIVISOR: u32 = 1000;
+
+    // Fixed fast settings for readings that can't depend on the user's: Vdd as the
+    // reference, with the 1.1V reference selected so read_vdd can measure it.
+    pub const VDD_REFERENCED: AdcSettings = AdcSettings {
+        resolution: Resolution::_10BIT,
+        sample_number: SampleNumber::ACC16,
+        samp_cap: true,
+        prescaler: Prescaler::DIV16,
+        adc_ref_voltage: AdcReferenceVoltage::VDDREF,
+        int_ref_voltage: IntReferenceVoltage::_1V1,
+        init_delay: InitDelay::DLY64,
+        asdv: false,
+        sample_delay: 0,
+        sample_length: 0,
+    };
+
+    // saved settings, or the defaults if they are out of range (erased or corrupt EEPROM)
+    pub fn new_with_settings(settings: &SavedSettings) -> Self {
+        let mut buf = [0; ADC_SETTINGS_SIZE];
+        settings.read_setting(Setting::AdcSettings, &mut buf);
+        Self::from_bytes(&buf).unwrap_or_default()
+    }
+
+    pub fn save(&self, settings: &mut SavedSettings) {
+        let buf = self.to_bytes();
+        let mut saved = [0; ADC_SETTINGS_SIZE];
+        settings.read_setting(Setting::AdcSettings, &mut saved);
+        if saved != buf {
+            settings.save_setting(Setting::AdcSettings, &buf);
+        }
+    }
+
+    // fields in declaration order, enums by their position in the EnumCycle order
+    fn to_bytes(self) -> [u8; ADC_SETTINGS_SIZE] {
+        [
+            self.resolution.to_u8(),
+            self.sample_number.to_u8(),
+            self.samp_cap as u8,
+            self.prescaler.to_u8(),
+            self.adc_ref_voltage.to_u8(),
+            self.int_ref_voltage.to_u8(),
+            self.init_delay.to_u8(),
+            self.asdv as u8,
+            self.sample_delay,
+            self.sample_length,
+        ]
+    }
+
+    fn from_bytes(buf: &[u8; ADC_SETTINGS_SIZE]) -> Option<Self> {
+        fn variant<T: EnumCycle>(value: u8) -> Option<T> {
+            (value < T::COUNT).then(|| T::from_u8(value))
+        }
+        fn flag(value: u8) -> Option<bool> {
+            (value <= 1).then_some(value != 0)
+        }
+        fn bounded(value: u8, max: u8) -> Option<u8> {
+            (value <= max).then_some(value)
+        }
+
+        Some(AdcSettings {
+            resolution: variant(buf[0])?,
+            sample_number: variant(buf[1])?,
+            samp_cap: flag(buf[2])?,
+            prescaler: variant(buf[3])?,
+            adc_ref_voltage: variant(buf[4])?,
+            int_ref_voltage: variant(buf[5])?,
+            init_delay: variant(buf[6])?,
+            asdv: flag(buf[7])?,
+            sample_delay: bounded(buf[8], SAMPLE_DELAY_MAX)?,
+            sample_length: bounded(buf[9], SAMPLE_LENGTH_MAX)?,
+        })
+    }
+
+    // Vdd-referenced readings are scaled by the measured vdd, in 10mV units
+    pub fn voltage_from_raw(&self, raw: u16, vdd: u16) -> u16 {
+        let raw = raw as u32;
+        let vrefe5 = if self.adc_ref_voltage == AdcReferenceVoltage::INTREF {
+            VREF_E5_VALUES[self.int_ref_voltage as usize]
+        } else {
+            vdd as u32 * Self::PRECISION_DIVISOR
+        };
+        let raw_max = match self.resolution {
+            Resolution::_10BIT => 1023, // 2^10 - 1
+            Resolution::_8BIT => 255,   // 2^8 - 1
+        };
+
+        (((raw * vrefe5) / raw_max) / Self::PRECISION_DIVISOR) as u16
+    }
+}
+
+#[cfg(target_arch = "avr")]
 pub struct Adc {
     pub channel: Option<AdcChannel>,
     pub settings: AdcSettings,
+    vdd: u16,
 
     adc0: Adc0,
     sigrow: Sigrow,
     vref: Vref,
 }
 
+#[cfg(target_arch = "avr")]
 impl Adc {
-    //const DECIMAL_PRECISION: u16 = 2; // X.YY
-    //const PRECISION_DIVISOR: u32 = 10u32.pow(5 - Self::DECIMAL_PRECISION as u32);
-    const PRECISION_DIVISOR: u32 = 1000;
-
     pub fn new(adc0: Adc0, sigrow: Sigrow, vref: Vref) -> Self {
         Self {
             channel: None,
             settings: AdcSettings::default(),
+            vdd: VDD_DEFAULT,
             adc0,
             sigrow,
             vref,
         }
     }
 
-    pub fn apply_settings(&mut self) {
-        self.vref
-            .ctrla()
-            .modify(|_, w| w.adc0refsel().variant(self.settings.int_ref_voltage));
+    pub fn seed_rand(&mut self) {
+        let mut sample_count: u32 = 0;
+        let mut seed_value: u32 = 0;
+        while sample_count < 4 {
+            if let Some(reading) = self.read_raw_nonblocking(AdcChannel::Temp) {
+                seed_value = (seed_value << 4) | (reading as u32 & 0b1111);
+                sample_count += 1;
+            }
+        }
+        sample_count = 0;
+        while sample_count < 4 {
+            #[cfg(not(feature = "board_v0"))]
+            if let Some(reading) = self.read_raw_nonblocking(AdcChannel::Vscl) {
+                seed_value = (seed_value << 4) | (reading as u32 & 0b1111);
+                sample_count += 1;
+            }
+            #[cfg(feature = "board_v0")]
+            if let Some(reading) = self.read_raw_nonblocking(AdcChannel::Vext) {
+                seed_value = (seed_value << 4) | (reading as u32 & 0b1111);
+                sample_count += 1;
+            }
+        }
+        crate::Rand::seed(seed_value);
+    }
+
+    fn temp_from_raw(&self, raw: u16, use_f: bool) -> u16 {
+        let sigrow_offset = self.sigrow.tempsense1().read().bits() as i8;
+        let sigrow_gain = self.sigrow.tempsense0().read().bits();
+
+        let mut temp: u32 = ((raw as i32) - (sigrow_offset as i32)) as u32;
+        temp = (temp as i32 * sigrow_gain as i32) as u32;
+        temp += 0x80;
+        temp >>= 8;
+        let temp_k = temp as u16;
+        let temp_c = temp_k.saturating_sub(273); // TODO <0
+        if use_f {
+            let temp_f = (temp_c as u32 * 9 / 5) + 32;
+            temp_f as u16
+        } else {
+            temp_c
+        }
+    }
+
+    // the 1.1V reference read against Vdd, inverted
+    fn vdd_from_raw(&self, raw: u16) -> u16 {
+        let vrefe5 = VREF_E5_VALUES[IntReferenceVoltage::_1V1 as usize];
+        (vrefe5 * 1023 / AdcSettings::PRECISION_DIVISOR)
+            .checked_div(raw as u32)
+            .map_or(VDD_DEFAULT, |vdd| vdd as u16)
+    }
+}
+
+#[cfg(target_arch = "avr")]
+impl AnalogSource for Adc {
+    fn settings(&self) -> &AdcSettings {
+        &self.settings
+    }
+
+    fn settings_mut(&mut self) -> &mut AdcSettings {
+        &mut self.settings
+    }
+
+    fn apply_settings(&mut self) {
+        self.vref.ctrla().modify(|_, w| {
+            w.adc0refsel()
+                .variant(pac_variant!(self.settings.int_ref_voltage))
+        });
 
         self.adc0.ctrla().write(|w| {
-            w.ressel().variant(self.settings.resolution);
+            w.ressel().variant(pac_variant!(self.settings.resolution));
             w.enable().set_bit()
         });
 
-        self.adc0
-            .ctrlb()
-            .write(|w| w.sampnum().variant(self.settings.sample_number));
+        self.adc0.ctrlb().write(|w| {
+            w.sampnum()
+                .variant(pac_variant!(self.settings.sample_number))
+        });
 
         self.adc0.ctrlc().write(|w| {
             w.sampcap().bit(self.settings.samp_cap);
-            w.refsel().variant(self.settings.adc_ref_voltage);
-            w.presc().variant(self.settings.prescaler)
+            w.refsel()
+                .variant(pac_variant!(self.settings.adc_ref_voltage));
+            w.presc().variant(pac_variant!(self.settings.prescaler))
         });
 
         self.adc0.ctrld().write(|w| {
-            w.initdly().variant(self.settings.init_delay);
+            w.initdly().variant(pac_variant!(self.settings.init_delay));
             w.asdv().bit(self.settings.asdv);
             w.sampdly().set(self.settings.sample_delay)
         });
@@ -155,12 +398,44 @@ impl Adc {
             .write(|w| w.samplen().set(self.settings.sample_length));
     }
 
-    pub fn disable(&mut self) {
+    fn disable(&mut self) {
         self.adc0.ctrla().write(|w| w.enable().clear_bit());
         self.channel = None;
     }
 
-    pub fn read_raw_nonblocking(&mut self, channel: AdcChannel) -> Option<u16> {
+    fn vdd(&self) -> u16 {
+        self.vdd
+    }
+
+    fn read_vdd(&mut self) -> u16 {
+        // let a running conversion finish, its channel is selected again on the next read
+        while self.adc0.command().read().stconv().bit_is_set() {}
+        let enabled = self.adc0.ctrla().read().enable().bit_is_set();
+
+        // fixed fast settings, the check takes the same short time whatever the user's are
+        let old_settings = self.settings;
+        self.settings = AdcSettings::VDD_REFERENCED;
+        self.apply_settings();
+        self.adc0
+            .muxpos()
+            .write(|w| w.muxpos().variant(adc0::muxpos::MUXPOS_A::INTREF));
+        self.adc0.command().write(|w| w.stconv().set_bit());
+        while self.adc0.command().read().stconv().bit_is_set() {}
+        let acc_divisor = 1 << (self.settings.sample_number as u8);
+        let raw = self.adc0.res().read().bits() / acc_divisor;
+
+        self.settings = old_settings;
+        if enabled {
+            self.apply_settings();
+        } else {
+            self.disable();
+        }
+        self.channel = None;
+        self.vdd = self.vdd_from_raw(raw);
+        self.vdd
+    }
+
+    fn read_raw_nonblocking(&mut self, channel: AdcChannel) -> Option<u16> {
         if self.adc0.command().read().stconv().bit_is_set() {
             return None;
         }
@@ -196,6 +471,8 @@ impl Adc {
             self.adc0.muxpos().write(|w| w.muxpos().variant(muxpos));
             self.adc0.command().write(|w| w.stconv().set_bit());
             self.channel = Some(channel);
+            // the result still holds the last channel's reading (or read_vdd's)
+            return None;
         }
 
         let acc_divisor = 1 << (self.settings.sample_number as u8); // TODO: VERIFY THIS
@@ -204,70 +481,7 @@ impl Adc {
         Some(raw)
     }
 
-    pub fn read_temp_nonblocking(&mut self, use_f: bool) -> Option<u16> {
+    fn read_temp_nonblocking(&mut self, use_f: bool) -> Option<u16> {
         self.read_raw_nonblocking(AdcChannel::Temp).map(|raw| self.temp_from_raw(raw, use_f))
     }
-
-    pub fn read_voltage_nonblocking(&mut self, channel: AdcChannel) -> Option<u16> {
-        self.read_raw_nonblocking(channel).map(|raw| self.voltage_from_raw(raw))
-    }
-
-    pub fn seed_rand(&mut self) {
-        let mut sample_count: u32 = 0;
-        let mut seed_value: u32 = 0;
-        while sample_count < 4 {
-            if let Some(reading) = self.read_raw_nonblocking(AdcChannel::Temp) {
-                seed_value = (seed_value << 4) | (reading as u32 & 0b1111);
-                sample_count += 1;
-            }
-        }
-        sample_count = 0;
-        while sample_count < 4 {
-            #[cfg(not(feature = "board_v0"))]
-            if let Some(reading) = self.read_raw_nonblocking(AdcChannel::Vscl) {
-                seed_value = (seed_value << 4) | (reading as u32 & 0b1111);
-                sample_count += 1;
-            }
-            #[cfg(feature = "board_v0")]
-            if let Some(reading) = self.read_raw_nonblocking(AdcChannel::Vext) {
-                seed_value = (seed_value << 4) | (reading as u32 & 0b1111);
-                sample_count += 1;
-            }
-
-        }
-        crate::Rand::seed(seed_value);
-    }
-
-    fn voltage_from_raw(&self, raw: u16) -> u16 {
-        let raw = raw as u32;
-        let vrefe5 = if self.settings.adc_ref_voltage == AdcReferenceVoltage::INTREF {
-            VREF_E5_VALUES[self.settings.int_ref_voltage as usize]
-        } else {
-            VREF_VDD_VALUE
-        };
-        let raw_max = match self.settings.resolution {
-            Resolution::_10BIT => 1023, // 2^10 - 1
-            Resolution::_8BIT => 255,   // 2^8 - 1
-        };
-
-        (((raw * vrefe5) / raw_max) / Self::PRECISION_DIVISOR) as u16
-    }
-
-    fn temp_from_raw(&mut self, raw: u16, use_f: bool) -> u16 {
-        let sigrow_offset = self.sigrow.tempsense1().read().bits() as i8;
-        let sigrow_gain = self.sigrow.tempsense0().read().bits();
-
-        let mut temp: u32 = ((raw as i32) - (sigrow_offset as i32)) as u32;
-        temp = (temp as i32 * sigrow_gain as i32) as u32;
-        temp += 0x80;
-        temp >>= 8;
-        let temp_k = temp as u16;
-        let temp_c = temp_k.saturating_sub(273); // TODO <0
-        if use_f {
-            let temp_f = (temp_c as u32 * 9 / 5) + 32;
-            temp_f as u16
-        } else {
-            temp_c
-        }
-    }
 }
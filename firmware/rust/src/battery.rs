@@ -0,0 +1,120 @@
+use crate::{
+    board::{AnalogSource, Buzzer},
+    impl_enum_cycle,
+    utils::EnumCycle,
+    SavedSettings, Setting,
+};
+
+// Vdd is the coin cell's voltage, checked about once a minute from the main loop. Below
+// LOW_PERCENT every check beeps, whatever mode is running, or shows LOW_FRAME for a second
+// when it can't beep (see Context::battery_tick).
+const CHECK_TICKS: u16 = 12000; // 60s of 5ms ticks
+const LOW_PERCENT: u8 = 5;
+const LOW_TONE_HZ: u32 = 2000;
+pub const LOW_TONE_MS: u16 = 300;
+pub const LOW_FRAME: &[u8; 8] = b"Lo batt!";
+pub const LOW_FRAME_TICKS: u8 = 200; // 1s of 5ms ticks
+
+// discharge curves as (Vdd in 10mV units, percent), highest first
+const LIR2032_CURVE: [(u16, u8); 9] = [
+    (420, 100),
+    (410, 90),
+    (400, 80),
+    (390, 68),
+    (380, 55),
+    (370, 40),
+    (360, 20),
+    (350, 8),
+    (330, 0),
+];
+const CR2032_CURVE: [(u16, u8); 8] = [
+    (300, 100),
+    (290, 80),
+    (280, 60),
+    (270, 40),
+    (260, 25),
+    (250, 15),
+    (240, 8),
+    (220, 0),
+];
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum BatteryCell {
+    Lir2032, // rechargeable, 4.2V full
+    Cr2032,  // primary, 3.0V full
+}
+
+impl_enum_cycle!(BatteryCell, 2);
+
+impl BatteryCell {
+    pub fn label(self) -> &'static [u8] {
+        match self {
+            BatteryCell::Lir2032 => b"LIR:",
+            BatteryCell::Cr2032 => b"CR:",
+        }
+    }
+
+    // linear between the curve's points
+    pub fn percent(self, vdd: u16) -> u8 {
+        let curve: &[(u16, u8)] = match self {
+            BatteryCell::Lir2032 => &LIR2032_CURVE,
+            BatteryCell::Cr2032 => &CR2032_CURVE,
+        };
+        if vdd >= curve[0].0 {
+            return 100;
+        }
+        curve
+            .windows(2)
+            .find(|points| vdd >= points[1].0)
+            .map_or(0, |points| {
+                let ((high_vdd, high), (low_vdd, low)) = (points[0], points[1]);
+                low + ((vdd - low_vdd) * (high - low) as u16 / (high_vdd - low_vdd)) as u8
+            })
+    }
+}
+
+pub struct Battery {
+    cell: BatteryCell,
+    ticks: u16,
+}
+
+impl Battery {
+    pub fn new_with_settings(settings: &SavedSettings) -> Self {
+        let cell = settings.read_setting_byte(Setting::BatteryCell);
+        Battery {
+            cell: if cell < BatteryCell::COUNT {
+                BatteryCell::from_u8(cell)
+            } else {
+                BatteryCell::Lir2032
+            },
+            ticks: 0,
+        }
+    }
+
+    pub fn cell(&self) -> BatteryCell {
+        self.cell
+    }
+
+    pub fn next_cell(&mut self, settings: &mut SavedSettings) {
+        self.cell = self.cell.next_wrapping();
+        settings.save_setting_byte(Setting::BatteryCell, self.cell.to_u8());
+    }
+
+    pub fn percent(&self, vdd: u16) -> u8 {
+        self.cell.percent(vdd)
+    }
+
+    // checks right away after power on, so Vdd-referenced readings are corrected early,
+    // true when the check finds the cell nearly flat
+    pub fn tick(&mut self, adc: &mut impl AnalogSource) -> bool {
+        let low = self.ticks == 0 && self.percent(adc.read_vdd()) < LOW_PERCENT;
+        self.ticks = (self.ticks + 1) % CHECK_TICKS;
+        low
+    }
+
+    pub fn beep(buzzer: &mut impl Buzzer) {
+        buzzer.tone(LOW_TONE_HZ, LOW_TONE_MS as u32);
+    }
+}
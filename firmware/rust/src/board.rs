@@ -0,0 +1,95 @@
+// Small hardware traits the modes are written against, so every ModeHandler can run on
+// other boards or against mock peripherals (see firmware/sim) without any changes.
+
+#[cfg(not(feature = "no_i2cutils"))]
+use crate::i2c::{ClientReceiveHandler, ClientRequestHandler, Direction, Error, SniffEvent};
+use crate::{
+    adc::{AdcChannel, AdcSettings},
+    DisplayPeakCurrent,
+};
+
+// HCMS-29xx style dot matrix display, NUM_CHARS characters of 5x7 dots
+pub trait DisplaySink {
+    type Error: core::fmt::Debug;
+
+    fn print_ascii_bytes(&mut self, bytes: &[u8]) -> Result<(), Self::Error>;
+    fn print_cols(&mut self, cols: &[u8]) -> Result<(), Self::Error>;
+    fn set_brightness(&mut self, brightness: u8) -> Result<(), Self::Error>;
+    fn set_peak_current(&mut self, peak_current: DisplayPeakCurrent) -> Result<(), Self::Error>;
+}
+
+// Piezo buzzer, duration of 0 plays until no_tone
+pub trait Buzzer {
+    fn tone(&mut self, frequency: u32, duration: u32);
+    fn no_tone(&mut self);
+}
+
+// ADC with the channels and settings of the ATtiny1604's ADC0
+pub trait AnalogSource {
+    fn settings(&self) -> &AdcSettings;
+    fn settings_mut(&mut self) -> &mut AdcSettings;
+    fn apply_settings(&mut self);
+    fn disable(&mut self);
+    fn read_raw_nonblocking(&mut self, channel: AdcChannel) -> Option<u16>;
+    fn read_temp_nonblocking(&mut self, use_f: bool) -> Option<u16>;
+    // Vdd in 10mV units as of the last read_vdd
+    fn vdd(&self) -> u16;
+    // measures Vdd from the internal 1.1V reference read against it, blocks until done
+    fn read_vdd(&mut self) -> u16;
+
+    fn read_voltage_nonblocking(&mut self, channel: AdcChannel) -> Option<u16> {
+        self.read_raw_nonblocking(channel)
+            .map(|raw| self.settings().voltage_from_raw(raw, self.vdd()))
+    }
+}
+
+// I2C peripheral usable as both host and client on the JST port
+#[cfg(not(feature = "no_i2cutils"))]
+pub trait I2cBus {
+    fn pins_to_pull_up(&mut self);
+    fn pins_to_floating(&mut self);
+
+    // speed in Hz, rise time of SDA/SCL with the bus's pull-ups and capacitance
+    fn host_setup(&mut self, speed: u32, rise_time_ns: u16);
+    fn host_end(&mut self);
+    // frees a bus left with SDA held low (9 clocks and a STOP), leaves the host disabled
+    // and the pins pulled up, returns whether both lines are high afterwards
+    fn bus_recover(&mut self) -> bool;
+    fn host_ping_device(&mut self, address: u8, direction: Direction) -> Result<bool, Error>;
+    fn host_write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Error>;
+    fn host_read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Error>;
+    fn host_write_read(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Error>;
+
+    fn client_setup(&mut self, address: u8);
+    fn client_end(&mut self);
+    fn client_available(&self) -> u8;
+    fn client_read(&mut self) -> Option<u8>;
+    // response sent for every host read until replaced, empty to NAK reads
+    fn client_write(&mut self, bytes: &[u8]);
+    // handlers called from the interrupt as the host writes and reads, see i2c.rs
+    fn client_set_handlers(
+        &mut self,
+        on_receive: Option<ClientReceiveHandler>,
+        on_request: Option<ClientRequestHandler>,
+    );
+
+    // Client on every address logging bus traffic, stopped with client_end. Only the
+    // addresses are logged unless ack_writes, the client has to ACK a write to see its
+    // bytes and then answers for every address like a device would.
+    fn sniff_setup(&mut self, ack_writes: bool);
+    fn sniff_read(&mut self) -> Option<SniffEvent>;
+}
+
+// The set of peripherals a board provides to the modes
+pub trait Board {
+    type Display: DisplaySink;
+    type Buzzer: Buzzer;
+    type Adc: AnalogSource;
+    #[cfg(not(feature = "no_i2cutils"))]
+    type I2c: I2cBus;
+}
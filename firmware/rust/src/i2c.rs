@@ -1,37 +1,92 @@
 #![allow(dead_code)]
 
+#[cfg(target_arch = "avr")]
+use crate::board::I2cBus;
+#[cfg(target_arch = "avr")]
 use avrxmega_hal::{
     clock::Clock,
+    delay::Delay,
     port::{mode::*, Pin, PinOps, PB0, PB1},
 };
+#[cfg(target_arch = "avr")]
 use core::cell::RefCell;
+#[cfg(target_arch = "avr")]
+use embedded_hal::delay::DelayNs;
 
-pub const I2C_BUS_SPEED: u32 = 100_000; // 100kHz
+// defaults until changed in I2C Util, the rise time is an estimate for the JST port
+// pull-ups and short cables
+pub const I2C_BUS_SPEED: BusSpeed = BusSpeed::Standard;
+pub const I2C_RISE_TIME_NS: u16 = 350;
 
+#[cfg(target_arch = "avr")]
 type SdaPin = PB1;
+#[cfg(target_arch = "avr")]
 type SclPin = PB0;
+#[cfg(target_arch = "avr")]
 type Twi = avrxmega_hal::pac::TWI0;
 
+#[cfg(target_arch = "avr")]
 const fn add_read_bit(address: u8) -> u8 {
     address | 0x01
 }
+#[cfg(target_arch = "avr")]
 const fn add_write_bit(address: u8) -> u8 {
     address & !0x01
 }
-const fn twi_baud(freq: u32, t_rise: u32) -> u32 {
-    ((crate::CoreClock::FREQ / freq) / 2)
-        - (5 + (((crate::CoreClock::FREQ / 1_000_000) * t_rise) / 2000))
+// One poll of a bounded wait, false once the wait has run out
+#[cfg(target_arch = "avr")]
+fn wait_poll(remaining_us: &mut u16) -> bool {
+    if *remaining_us == 0 {
+        return false;
+    }
+    *remaining_us -= 1;
+    Delay::<crate::CoreClock>::new().delay_us(1);
+    true
+}
+#[cfg(target_arch = "avr")]
+const fn twi_baud(freq: u32, t_rise: u32) -> u8 {
+    // the fastest speeds can need a negative value, 0 gets as close as the clock allows
+    let baud = ((crate::CoreClock::FREQ / freq) / 2)
+        .saturating_sub(5 + (((crate::CoreClock::FREQ / 1_000_000) * t_rise) / 2000));
+    if baud > u8::MAX as u32 {
+        u8::MAX
+    } else {
+        baud as u8
+    }
 }
 
-// TODO: investigate issues with chunking data and consecutive writes from host
-// for now, specific feature enables large buffer for use as I2C client
-#[cfg(feature = "i2c_client")]
-pub const I2C_BUFFER_SIZE: usize = 200;
-#[cfg(not(feature = "i2c_client"))]
+// host waits poll the TWI about once a microsecond and give up after this long, so a
+// missing or misbehaving device can't hang the main loop. A transfer takes at most the
+// busy wait plus one byte wait per byte.
+const HOST_BUSY_TIMEOUT_US: u16 = 1_000; // another host finishing its transfer
+const HOST_BYTE_TIMEOUT_US: u16 = 5_000; // each byte, leaves room for clock stretching
+
+// largest single transfer, longer client messages are sent in chunks (see i2c_message.rs)
 pub const I2C_BUFFER_SIZE: usize = 32;
+
+// Client handlers, called from the TWI interrupt so they have to be quick and must not use
+// the I2c instance themselves. The receive handler gets everything a host wrote once it
+// sends STOP (or a repeated START), the data is then gone instead of waiting for
+// client_read. The request handler fills in the response when a host starts reading and
+// returns its length, 0 NAKs the read. Without it the client_write response is sent.
+pub type ClientReceiveHandler = fn(data: &[u8]);
+pub type ClientRequestHandler = fn(response: &mut [u8]) -> usize;
+
+// Sniffed events are queued 2 bytes each (kind, value) in the data buffer
+#[cfg(target_arch = "avr")]
+const SNIFF_START_WRITE: u8 = 0;
+#[cfg(target_arch = "avr")]
+const SNIFF_START_READ: u8 = 1;
+#[cfg(target_arch = "avr")]
+const SNIFF_DATA: u8 = 2;
+#[cfg(target_arch = "avr")]
+const SNIFF_DROPPED: u8 = 3;
+
+#[cfg(target_arch = "avr")]
 static I2C_STATE: avr_device::interrupt::Mutex<RefCell<Option<I2cState>>> =
     avr_device::interrupt::Mutex::new(RefCell::new(None));
 
+#[cfg(target_arch = "avr")]
 struct I2cState {
     twi: Twi,
     twi_init: bool,
@@ -44,10 +99,24 @@ struct I2cState {
     bytes_to_process: u8,
     bytes_processed: u8,
     bytes_transmitted: u8, // client response
+    response: [u8; I2C_BUFFER_SIZE],
+    response_len: u8,
     client_check_nak: bool,
     host_data_sent: bool,
+    // current host transfer, the address goes out with the first read or write
+    host_direction: Direction,
+    host_address_sent: bool,
+    on_receive: Option<ClientReceiveHandler>,
+    on_request: Option<ClientRequestHandler>,
+    // client listening to every address, see raw_setup_sniff
+    sniff: bool,
+    sniff_ack: bool,
+    sniff_head: u8,
+    sniff_len: u8,
+    sniff_dropped: bool,
 }
 
+#[cfg(target_arch = "avr")]
 impl I2cState {
     fn new(
         twi: Twi,
@@ -65,8 +134,19 @@ impl I2cState {
             bytes_to_process: 0,
             bytes_processed: 0,
             bytes_transmitted: 0,
+            response: [0; I2C_BUFFER_SIZE],
+            response_len: 0,
             client_check_nak: false,
             host_data_sent: false,
+            host_direction: Direction::Write,
+            host_address_sent: false,
+            on_receive: None,
+            on_request: None,
+            sniff: false,
+            sniff_ack: false,
+            sniff_head: 0,
+            sniff_len: 0,
+            sniff_dropped: false,
         }
     }
 
@@ -96,6 +176,98 @@ impl I2cState {
     fn pins_to_floating(&mut self) {
         self.set_pin_mode(false);
     }
+
+    // Queues a sniffed event, when the queue is full it's dropped and a Dropped event goes
+    // in first once there is room again
+    fn sniff_push(&mut self, kind: u8, value: u8) {
+        let needed = if self.sniff_dropped { 4 } else { 2 };
+        if self.sniff_len + needed > I2C_BUFFER_SIZE as u8 {
+            self.sniff_dropped = true;
+            return;
+        }
+        if self.sniff_dropped {
+            self.sniff_dropped = false;
+            self.sniff_put(SNIFF_DROPPED, 0);
+        }
+        self.sniff_put(kind, value);
+    }
+
+    fn sniff_put(&mut self, kind: u8, value: u8) {
+        let index = (self.sniff_head + self.sniff_len) as usize % I2C_BUFFER_SIZE;
+        self.data[index] = kind;
+        self.data[index + 1] = value;
+        self.sniff_len += 2;
+    }
+
+    fn sniff_pop(&mut self) -> Option<SniffEvent> {
+        if self.sniff_len == 0 {
+            return None;
+        }
+        let index = self.sniff_head as usize;
+        let (kind, value) = (self.data[index], self.data[index + 1]);
+        self.sniff_head = ((index + 2) % I2C_BUFFER_SIZE) as u8;
+        self.sniff_len -= 2;
+
+        Some(match kind {
+            SNIFF_START_WRITE => SniffEvent::Start(value, Direction::Write),
+            SNIFF_START_READ => SniffEvent::Start(value, Direction::Read),
+            SNIFF_DATA => SniffEvent::Data(value),
+            _ => SniffEvent::Dropped,
+        })
+    }
+
+    // Disables the host, frees the bus and starts over, for when a transfer stopped making
+    // progress. The bus speed set up before is kept.
+    fn host_reset(&mut self) {
+        self.twi.mctrla().write(|w| w.enable().clear_bit());
+        self.recover_bus();
+        self.twi.mctrla().write(|w| w.enable().set_bit());
+        self.twi
+            .mstatus()
+            .write(|w| w.busstate().idle().busstate().idle());
+        self.host_address_sent = false;
+        self.bytes_to_process = 0;
+        self.bytes_processed = 0;
+    }
+
+    // Clocks SCL by hand until a device holding SDA low lets go, at most the 9 clocks it
+    // takes to finish its byte and the ACK, then sends a STOP. Lines are either driven low
+    // or left to the pull-ups, like the open drain outputs of the TWI. Needs the TWI off.
+    fn recover_bus(&mut self) -> bool {
+        const HALF_PERIOD_US: u32 = 5; // 100kHz
+        let mut delay = Delay::<crate::CoreClock>::new();
+
+        let (Some(sda), Some(scl)) = (self.sda.take(), self.scl.take()) else {
+            return false;
+        };
+        let sda = sda.into_pull_up_input();
+        let mut scl = scl.into_pull_up_input();
+        delay.delay_us(HALF_PERIOD_US);
+
+        for _ in 0..9 {
+            if sda.is_high() {
+                break;
+            }
+            let scl_low = scl.into_output();
+            delay.delay_us(HALF_PERIOD_US);
+            scl = scl_low.into_pull_up_input();
+            delay.delay_us(HALF_PERIOD_US);
+        }
+
+        // STOP, SDA goes high while SCL is high
+        let scl_low = scl.into_output();
+        let sda_low = sda.into_output();
+        delay.delay_us(HALF_PERIOD_US);
+        let scl = scl_low.into_pull_up_input();
+        delay.delay_us(HALF_PERIOD_US);
+        let sda = sda_low.into_pull_up_input();
+        delay.delay_us(HALF_PERIOD_US);
+
+        let released = sda.is_high() && scl.is_high();
+        self.sda = Some(sda.forget_imode());
+        self.scl = Some(scl.forget_imode());
+        released
+    }
 }
 
 /// I2C Error
@@ -116,6 +288,67 @@ pub enum Error {
     Uninit,
     /// Buffer overflow, too many bytes to process
     BufferOverflow,
+    /// A started transfer stopped making progress, the bus was reset
+    Timeout,
+    /// Bus stayed busy with another host's transfer (or a line held low)
+    BusBusy,
+}
+
+impl embedded_hal::i2c::Error for Error {
+    fn kind(&self) -> embedded_hal::i2c::ErrorKind {
+        use embedded_hal::i2c::{ErrorKind, NoAcknowledgeSource};
+        match self {
+            Error::ArbitrationLost => ErrorKind::ArbitrationLoss,
+            Error::AddressNack => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address),
+            Error::DataNack => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data),
+            Error::Bus => ErrorKind::Bus,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+/// I2C Bus Speed
+#[derive(ufmt::derive::uDebug, Debug, Clone, Copy, Eq, PartialEq)]
+#[repr(u8)]
+pub enum BusSpeed {
+    /// 100kHz
+    Standard,
+    /// 400kHz
+    Fast,
+    /// 1MHz
+    FastPlus,
+}
+
+impl BusSpeed {
+    pub const ALL: [BusSpeed; 3] = [BusSpeed::Standard, BusSpeed::Fast, BusSpeed::FastPlus];
+
+    pub const fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(BusSpeed::Standard),
+            1 => Some(BusSpeed::Fast),
+            2 => Some(BusSpeed::FastPlus),
+            _ => None,
+        }
+    }
+
+    pub const fn hz(self) -> u32 {
+        match self {
+            BusSpeed::Standard => 100_000,
+            BusSpeed::Fast => 400_000,
+            BusSpeed::FastPlus => 1_000_000,
+        }
+    }
+}
+
+/// Bus traffic seen while sniffing
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SniffEvent {
+    /// START or repeated START with the 7 bit address
+    Start(u8, Direction),
+    /// Byte written by the host
+    Data(u8),
+    /// Events were lost while the queue was full
+    Dropped,
 }
 
 /// I2C Transfer Direction
@@ -128,8 +361,10 @@ pub enum Direction {
     Read,
 }
 
+#[cfg(target_arch = "avr")]
 pub struct I2c {}
 
+#[cfg(target_arch = "avr")]
 impl I2c
 where
     SdaPin: PinOps,
@@ -150,100 +385,23 @@ where
         Self {}
     }
 
-    pub fn pins_to_pull_up(&mut self) {
-        avr_device::interrupt::free(|cs| {
-            let mut state_opt = I2C_STATE.borrow(cs).borrow_mut();
-            let state = state_opt.as_mut().unwrap();
-            state.pins_to_pull_up();
-        });
-    }
-
-    pub fn pins_to_floating(&mut self) {
-        avr_device::interrupt::free(|cs| {
-            let mut state_opt = I2C_STATE.borrow(cs).borrow_mut();
-            let state = state_opt.as_mut().unwrap();
-            state.pins_to_floating();
-        });
-    }
-
-    //
-    // HOST PUBLIC
-    //
-    pub fn host_setup(&mut self, speed: u32) {
-        self.raw_setup(speed);
-    }
-
-    pub fn host_end(&mut self) {
-        self.raw_end();
-    }
-
-    pub fn host_ping_device(&mut self, address: u8, direction: Direction) -> Result<bool, Error> {
-        self.raw_start(address, direction)?;
-        match self.raw_stop() {
-            Ok(_) => Ok(true),
-            Err(Error::AddressNack) => Ok(false),
-            Err(e) => Err(e),
-        }
-    }
-
-    pub fn host_write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Error> {
-        self.raw_start(address, Direction::Write)?;
-        self.raw_write(bytes)?;
-        self.raw_stop()?;
-        Ok(())
-    }
-
-    pub fn host_read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Error> {
-        self.raw_start(address, Direction::Read)?;
-        self.raw_read(buffer, true)?;
-        self.raw_stop()?;
-        Ok(())
-    }
-
-    pub fn host_write_read(
-        &mut self,
-        address: u8,
-        bytes: &[u8],
-        buffer: &mut [u8],
-    ) -> Result<(), Error> {
-        self.raw_start(address, Direction::Write)?;
-        self.raw_write(bytes)?;
-        self.raw_start(address, Direction::Read)?;
-        self.raw_read(buffer, true)?;
-        self.raw_stop()?;
-        Ok(())
-    }
-
-    //
-    // CLIENT PUBLIC
-    //
-    pub fn client_setup(&mut self, address: u8) {
-        self.raw_setup_client(address);
-    }
-
-    pub fn client_end(&mut self) {
-        self.raw_end_client();
-    }
-
-    pub fn client_available(&self) -> u8 {
-        self.raw_available_client()
-    }
-
-    pub fn client_read(&mut self) -> Option<u8> {
-        self.raw_read_client()
-    }
-
     //
     // HOST
     //
-    fn raw_setup(&mut self, speed: u32) {
+    fn raw_setup(&mut self, speed: u32, rise_time_ns: u16) {
         avr_device::interrupt::free(|cs| {
             let mut state_opt = I2C_STATE.borrow(cs).borrow_mut();
             let state = state_opt.as_mut().unwrap();
 
             state.pins_to_pull_up();
 
-            let baud = twi_baud(speed, 350) as u8; // hard-coded rise time estimate for now
+            // baud can only change with the host disabled, setup is also used to change speed
+            let baud = twi_baud(speed, rise_time_ns as u32);
+            state.twi.mctrla().write(|w| w.enable().clear_bit());
+            state
+                .twi
+                .ctrla()
+                .modify(|_, w| w.fmpen().bit(speed > BusSpeed::Fast.hz()));
             state.twi.mbaud().write(|w| w.set(baud));
             state.twi.mctrla().write(|w| w.enable().set_bit());
             state
@@ -253,7 +411,10 @@ where
         });
     }
 
-    fn raw_start(&mut self, address: u8, _direction: Direction) -> Result<(), Error> {
+    // Starts a transfer in the given direction. The address goes out with the first byte
+    // written or read, or at raw_stop for a transfer without data, so a write followed by
+    // a read goes out with a repeated START in between instead of a STOP.
+    fn raw_start(&mut self, address: u8, direction: Direction) -> Result<(), Error> {
         avr_device::interrupt::free(|cs| {
             let mut state_opt = I2C_STATE.borrow(cs).borrow_mut();
             let state = state_opt.as_mut().unwrap();
@@ -261,47 +422,48 @@ where
             state.client_address = address << 1; // byte 0 is rw bit, 0 for write
             state.bytes_to_process = 0;
             state.bytes_processed = 0;
+            state.host_direction = direction;
+            state.host_address_sent = false;
 
             Ok(())
         })
     }
 
+    // sends the bytes straight away and keeps the bus for more, any length
     fn raw_write(&mut self, bytes: &[u8]) -> Result<(), Error> {
-        avr_device::interrupt::free(|cs| {
-            let mut state_opt = I2C_STATE.borrow(cs).borrow_mut();
-            let state = state_opt.as_mut().unwrap();
-
-            let buf_start = state.bytes_to_process as usize;
-            let buf_end = buf_start + bytes.len();
-            if buf_end >= I2C_BUFFER_SIZE {
-                return Err(Error::BufferOverflow);
-            }
-
-            state.data[buf_start..buf_end].copy_from_slice(bytes);
-            state.bytes_to_process += bytes.len() as u8;
-
-            Ok(())
-        })
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        self.host_transmit(bytes, false)
     }
 
-    fn raw_read(&mut self, buffer: &mut [u8], _last_read: bool) -> Result<(), Error> {
-        avr_device::interrupt::free(|cs| {
-            let mut state_opt = I2C_STATE.borrow(cs).borrow_mut();
-            let state = state_opt.as_mut().unwrap();
-
-            state.bytes_processed = 0;
-            state.bytes_to_process = buffer.len() as u8;
-            if state.bytes_to_process > I2C_BUFFER_SIZE as u8 {
-                state.bytes_to_process = I2C_BUFFER_SIZE as u8;
-            }
-
-            // TODO: receive bytes blocking
-            Ok(())
-        })
+    // Reads into buffer, ACKing every byte except the last one of the last read. That one
+    // is NAKed by whatever comes next, the STOP from raw_stop or a repeated START.
+    fn raw_read(&mut self, buffer: &mut [u8], last_read: bool) -> Result<(), Error> {
+        self.host_receive(buffer, last_read)
     }
 
     fn raw_stop(&mut self) -> Result<(), Error> {
-        self.host_transmit(true)
+        let (direction, address_sent) = avr_device::interrupt::free(|cs| {
+            let state_opt = I2C_STATE.borrow(cs).borrow();
+            let state = state_opt.as_ref().unwrap();
+            (state.host_direction, state.host_address_sent)
+        });
+
+        match direction {
+            // write with no data, only the address needs an ACK
+            Direction::Write if !address_sent => self.host_transmit(&[], true),
+            // read with no data, only the address needs an ACK
+            Direction::Read if !address_sent => {
+                self.host_receive(&mut [], true)?;
+                self.host_command_stop();
+                Ok(())
+            }
+            _ => {
+                self.host_command_stop();
+                Ok(())
+            }
+        }
     }
 
     fn raw_end(&mut self) {
@@ -340,6 +502,50 @@ where
         });
     }
 
+    // Listens to every address through the address mask (ADDREN off, all bits don't care).
+    // The TWI client can't watch a transfer it doesn't take part in, every address is
+    // NAKed and only logged unless ack_writes. Then writes are ACKed so their bytes can be
+    // logged, which also makes devices that aren't there look present and ACKs bytes a
+    // device meant to NAK. Reads are always NAKed so SDA is left to the device answering.
+    fn raw_setup_sniff(&mut self, ack_writes: bool) {
+        avr_device::interrupt::free(|cs| {
+            let mut state_opt = I2C_STATE.borrow(cs).borrow_mut();
+            let state = state_opt.as_mut().unwrap();
+
+            state.pins_to_pull_up();
+
+            state.sniff = true;
+            state.sniff_ack = ack_writes;
+            state.sniff_head = 0;
+            state.sniff_len = 0;
+            state.sniff_dropped = false;
+
+            state.twi.saddr().write(|w| w.set(0));
+            state
+                .twi
+                .saddrmask()
+                .write(|w| w.addrmask().set(0x7F).addren().clear_bit());
+            state.twi.sctrla().write(|w| {
+                w.dien()
+                    .set_bit()
+                    .apien()
+                    .set_bit()
+                    .pien()
+                    .set_bit()
+                    .enable()
+                    .set_bit()
+            });
+        });
+    }
+
+    fn raw_read_sniff(&mut self) -> Option<SniffEvent> {
+        avr_device::interrupt::free(|cs| {
+            let mut state_opt = I2C_STATE.borrow(cs).borrow_mut();
+            let state = state_opt.as_mut().unwrap();
+            state.sniff_pop()
+        })
+    }
+
     fn raw_available_client(&self) -> u8 {
         avr_device::interrupt::free(|cs| {
             let mut state_opt = I2C_STATE.borrow(cs).borrow_mut();
@@ -359,7 +565,7 @@ where
                 state.bytes_processed += 1;
                 Some(data)
             } else {
-                // clear buffer once all data is read (TODO: not needed with callback)
+                // clear buffer once all data is read
                 if state.bytes_processed > 0 {
                     state.bytes_to_process = 0;
                     state.bytes_processed = 0;
@@ -369,19 +575,63 @@ where
         })
     }
 
+    fn raw_write_client(&mut self, bytes: &[u8]) {
+        avr_device::interrupt::free(|cs| {
+            let mut state_opt = I2C_STATE.borrow(cs).borrow_mut();
+            let state = state_opt.as_mut().unwrap();
+
+            let len = bytes.len().min(I2C_BUFFER_SIZE);
+            state.response[..len].copy_from_slice(&bytes[..len]);
+            state.response_len = len as u8;
+        })
+    }
+
+    fn raw_set_client_handlers(
+        &mut self,
+        on_receive: Option<ClientReceiveHandler>,
+        on_request: Option<ClientRequestHandler>,
+    ) {
+        avr_device::interrupt::free(|cs| {
+            let mut state_opt = I2C_STATE.borrow(cs).borrow_mut();
+            let state = state_opt.as_mut().unwrap();
+
+            state.on_receive = on_receive;
+            state.on_request = on_request;
+        })
+    }
+
     fn raw_end_client(&mut self) {
         avr_device::interrupt::free(|cs| {
             let mut state_opt = I2C_STATE.borrow(cs).borrow_mut();
             let state = state_opt.as_mut().unwrap();
 
             state.twi.saddr().write(|w| w.set(0));
+            state.twi.saddrmask().write(|w| w.set(0));
             state.twi.sctrla().write(|w| w.enable().clear_bit());
+            state.sniff = false;
+            state.response_len = 0;
+            state.on_receive = None;
+            state.on_request = None;
 
             state.pins_to_floating();
         });
     }
 
-    fn host_transmit(&mut self, send_stop: bool) -> Result<(), Error> {
+    // NAKs the last byte if the host was reading and releases the bus
+    fn host_command_stop(&mut self) {
+        avr_device::interrupt::free(|cs| {
+            let mut state_opt = I2C_STATE.borrow(cs).borrow_mut();
+            let state = state_opt.as_mut().unwrap();
+            state
+                .twi
+                .mctrlb()
+                .write(|w| w.ackact().set_bit().mcmd().stop());
+        });
+    }
+
+    // Sends the address unless an earlier call did, then the bytes. The bus is kept after
+    // the last byte unless send_stop, so the next call can carry on with more bytes.
+    fn host_transmit(&mut self, bytes: &[u8], send_stop: bool) -> Result<(), Error> {
         avr_device::interrupt::free(|cs| {
             let mut state_opt = I2C_STATE.borrow(cs).borrow_mut();
             let state = state_opt.as_mut().unwrap();
@@ -391,8 +641,12 @@ where
                 return Err(Error::Uninit);
             }
 
-            let mut addr_sent = false;
-            let mut data_sent = false;
+            let mut addr_sent = state.host_address_sent;
+            // carrying on after an earlier call, any NACK is for its data
+            let mut data_sent = addr_sent;
+            let mut next = 0;
+            let mut busy_wait = HOST_BUSY_TIMEOUT_US;
+            let mut byte_wait = HOST_BYTE_TIMEOUT_US;
 
             let result = loop {
                 let status = state.twi.mstatus().read();
@@ -408,6 +662,9 @@ where
 
                 // wait for bus to be ready
                 if bus_state.is_busy() {
+                    if !wait_poll(&mut busy_wait) {
+                        break Err(Error::BusBusy);
+                    }
                     continue;
                 }
 
@@ -422,12 +679,17 @@ where
                 }
 
                 // wait for write to complete
-                if status.wif().bit_is_set() {
+                if status.wif().bit_is_clear() {
+                    if !wait_poll(&mut byte_wait) {
+                        break Err(Error::Timeout);
+                    }
+                } else {
+                    byte_wait = HOST_BYTE_TIMEOUT_US;
                     // check if we got a NACK
                     if status.rxack().bit_is_set() {
                         if data_sent {
                             // ignore NACK if all data was sent
-                            if state.bytes_to_process != 0 {
+                            if next < bytes.len() {
                                 break Err(Error::DataNack);
                             } else {
                                 break Ok(());
@@ -436,13 +698,9 @@ where
                             break Err(Error::AddressNack);
                         }
                     // else check if more bytes to send
-                    } else if state.bytes_to_process > 0 {
-                        state
-                            .twi
-                            .mdata()
-                            .write(|w| w.set(state.data[state.bytes_processed as usize]));
-                        state.bytes_processed += 1;
-                        state.bytes_to_process -= 1;
+                    } else if next < bytes.len() {
+                        state.twi.mdata().write(|w| w.set(bytes[next]));
+                        next += 1;
                         data_sent = true;
                     // break when no more bytes to send
                     } else {
@@ -451,8 +709,119 @@ where
                 }
             };
 
-            if send_stop || result.is_err() {
-                state.twi.mctrlb().write(|w| w.mcmd().stop());
+            state.host_address_sent = addr_sent;
+            match result {
+                // never got the bus, nothing to stop
+                Err(Error::BusBusy) => {}
+                Err(Error::Timeout) => state.host_reset(),
+                Err(_) => {
+                    state.twi.mctrlb().write(|w| w.mcmd().stop());
+                }
+                Ok(_) if send_stop => {
+                    state.twi.mctrlb().write(|w| w.mcmd().stop());
+                }
+                Ok(_) => {}
+            }
+
+            result
+        })
+    }
+
+    fn host_receive(&mut self, buffer: &mut [u8], last_read: bool) -> Result<(), Error> {
+        avr_device::interrupt::free(|cs| {
+            let mut state_opt = I2C_STATE.borrow(cs).borrow_mut();
+            let state = state_opt.as_mut().unwrap();
+
+            // if disabled, abort
+            if state.twi.mctrla().read().enable().bit_is_clear() {
+                return Err(Error::Uninit);
+            }
+
+            let mut bytes_received = 0;
+            let mut busy_wait = HOST_BUSY_TIMEOUT_US;
+            let mut byte_wait = HOST_BYTE_TIMEOUT_US;
+
+            let result = loop {
+                let status = state.twi.mstatus().read();
+                let bus_state = status.busstate();
+
+                if bus_state.is_unknown() {
+                    return Err(Error::Uninit);
+                }
+
+                if status.arblost().bit_is_set() {
+                    return Err(Error::ArbitrationLost);
+                }
+
+                if status.buserr().bit_is_set() {
+                    break Err(Error::Bus);
+                }
+
+                // wait for bus to be ready
+                if bus_state.is_busy() {
+                    if !wait_poll(&mut busy_wait) {
+                        break Err(Error::BusBusy);
+                    }
+                    continue;
+                }
+
+                // send address first, a START or a repeated START if we still own the bus
+                if !state.host_address_sent {
+                    state
+                        .twi
+                        .maddr()
+                        .write(|w| w.set(add_read_bit(state.client_address)));
+                    state.host_address_sent = true;
+                    continue;
+                }
+
+                // write flag on a read means the address was NAKed
+                if status.wif().bit_is_set() {
+                    break Err(Error::AddressNack);
+                }
+
+                // wait for the next byte, the clock is held until it is ACKed or NAKed
+                if status.rif().bit_is_clear() {
+                    if !wait_poll(&mut byte_wait) {
+                        break Err(Error::Timeout);
+                    }
+                } else {
+                    byte_wait = HOST_BYTE_TIMEOUT_US;
+
+                    // nothing to read (a ping), the byte is NAKed by the STOP
+                    if buffer.is_empty() {
+                        break Ok(());
+                    }
+
+                    buffer[bytes_received] = state.twi.mdata().read().bits();
+                    bytes_received += 1;
+
+                    if bytes_received == buffer.len() && last_read {
+                        // NAK goes out with the next command, STOP or repeated START
+                        state
+                            .twi
+                            .mctrlb()
+                            .write(|w| w.ackact().set_bit().mcmd().noact());
+                        break Ok(());
+                    }
+
+                    // ACK and clock in the next byte, the next read picks it up
+                    state
+                        .twi
+                        .mctrlb()
+                        .write(|w| w.ackact().clear_bit().mcmd().recvtrans());
+                    if bytes_received == buffer.len() {
+                        break Ok(());
+                    }
+                }
+            };
+
+            match result {
+                Err(Error::BusBusy) | Ok(_) => {}
+                Err(Error::Timeout) => state.host_reset(),
+                Err(_) => {
+                    state.twi.mctrlb().write(|w| w.mcmd().stop());
+                }
             }
 
             result
@@ -460,6 +829,174 @@ where
     }
 }
 
+#[cfg(target_arch = "avr")]
+impl I2cBus for I2c {
+    fn pins_to_pull_up(&mut self) {
+        avr_device::interrupt::free(|cs| {
+            let mut state_opt = I2C_STATE.borrow(cs).borrow_mut();
+            let state = state_opt.as_mut().unwrap();
+            state.pins_to_pull_up();
+        });
+    }
+
+    fn pins_to_floating(&mut self) {
+        avr_device::interrupt::free(|cs| {
+            let mut state_opt = I2C_STATE.borrow(cs).borrow_mut();
+            let state = state_opt.as_mut().unwrap();
+            state.pins_to_floating();
+        });
+    }
+
+    //
+    // HOST PUBLIC
+    //
+    fn host_setup(&mut self, speed: u32, rise_time_ns: u16) {
+        self.raw_setup(speed, rise_time_ns);
+    }
+
+    fn host_end(&mut self) {
+        self.raw_end();
+    }
+
+    fn bus_recover(&mut self) -> bool {
+        avr_device::interrupt::free(|cs| {
+            let mut state_opt = I2C_STATE.borrow(cs).borrow_mut();
+            let state = state_opt.as_mut().unwrap();
+
+            // pins are only ours with the host off, whatever transfer was going on is gone
+            state.twi.mctrla().write(|w| w.enable().clear_bit());
+            state.host_address_sent = false;
+            state.bytes_to_process = 0;
+            state.bytes_processed = 0;
+
+            state.recover_bus()
+        })
+    }
+
+    fn host_ping_device(&mut self, address: u8, direction: Direction) -> Result<bool, Error> {
+        self.raw_start(address, direction)?;
+        match self.raw_stop() {
+            Ok(_) => Ok(true),
+            Err(Error::AddressNack) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn host_write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Error> {
+        self.raw_start(address, Direction::Write)?;
+        self.raw_write(bytes)?;
+        self.raw_stop()?;
+        Ok(())
+    }
+
+    fn host_read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Error> {
+        self.raw_start(address, Direction::Read)?;
+        self.raw_read(buffer, true)?;
+        self.raw_stop()?;
+        Ok(())
+    }
+
+    fn host_write_read(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Error> {
+        self.raw_start(address, Direction::Write)?;
+        self.raw_write(bytes)?;
+        self.raw_start(address, Direction::Read)?;
+        self.raw_read(buffer, true)?;
+        self.raw_stop()?;
+        Ok(())
+    }
+
+    //
+    // CLIENT PUBLIC
+    //
+    fn client_setup(&mut self, address: u8) {
+        self.raw_setup_client(address);
+    }
+
+    fn client_end(&mut self) {
+        self.raw_end_client();
+    }
+
+    fn client_available(&self) -> u8 {
+        self.raw_available_client()
+    }
+
+    fn client_read(&mut self) -> Option<u8> {
+        self.raw_read_client()
+    }
+
+    fn client_write(&mut self, bytes: &[u8]) {
+        self.raw_write_client(bytes);
+    }
+
+    fn client_set_handlers(
+        &mut self,
+        on_receive: Option<ClientReceiveHandler>,
+        on_request: Option<ClientRequestHandler>,
+    ) {
+        self.raw_set_client_handlers(on_receive, on_request);
+    }
+
+    fn sniff_setup(&mut self, ack_writes: bool) {
+        self.raw_setup_sniff(ack_writes);
+    }
+
+    fn sniff_read(&mut self) -> Option<SniffEvent> {
+        self.raw_read_sniff()
+    }
+}
+
+// Lets driver crates for I2C sensors talk to devices on the JST port, set up the bus
+// with host_setup first
+#[cfg(target_arch = "avr")]
+impl embedded_hal::i2c::ErrorType for I2c {
+    type Error = Error;
+}
+
+#[cfg(target_arch = "avr")]
+impl embedded_hal::i2c::I2c for I2c {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [embedded_hal::i2c::Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        use embedded_hal::i2c::Operation;
+
+        if operations.is_empty() {
+            return Ok(());
+        }
+
+        // consecutive operations of the same kind share one START, a change of direction
+        // gets a repeated START
+        let mut direction = None;
+        for i in 0..operations.len() {
+            let next_is_read = matches!(operations.get(i + 1), Some(Operation::Read(_)));
+            match &mut operations[i] {
+                Operation::Write(bytes) => {
+                    if direction != Some(Direction::Write) {
+                        self.raw_start(address, Direction::Write)?;
+                        direction = Some(Direction::Write);
+                    }
+                    self.raw_write(bytes)?;
+                }
+                Operation::Read(buffer) => {
+                    if direction != Some(Direction::Read) {
+                        self.raw_start(address, Direction::Read)?;
+                        direction = Some(Direction::Read);
+                    }
+                    self.raw_read(buffer, !next_is_read)?;
+                }
+            }
+        }
+        self.raw_stop()
+    }
+}
+
+#[cfg(target_arch = "avr")]
 #[avr_device::interrupt(attiny1604)]
 fn TWI0_TWIS() {
     avr_device::interrupt::free(|cs| {
@@ -475,49 +1012,77 @@ fn TWI0_TWIS() {
         let mut response = Response::None;
         let client_status = state.twi.sstatus().read();
 
+        // sniffing, log what the host sends and otherwise keep off the bus
+        if state.sniff {
+            if client_status.apif().bit_is_set() {
+                if client_status.ap().bit_is_set() {
+                    let address = state.twi.sdata().read().bits();
+                    if client_status.dir().bit_is_set() {
+                        state.sniff_push(SNIFF_START_READ, address >> 1);
+                        response = Response::NakComplete;
+                    } else {
+                        state.sniff_push(SNIFF_START_WRITE, address >> 1);
+                        response = if state.sniff_ack {
+                            Response::AckContinue
+                        } else {
+                            Response::NakComplete
+                        };
+                    }
+                } else {
+                    response = Response::AckComplete;
+                }
+            } else if client_status.dif().bit_is_set() {
+                let data = state.twi.sdata().read().bits();
+                state.sniff_push(SNIFF_DATA, data);
+                response = Response::AckContinue;
+            }
+        }
         // address or stop condition detected
-        if client_status.apif().bit_is_set() {
+        else if client_status.apif().bit_is_set() {
             // host is done sending data
             if state.host_data_sent {
                 state.host_data_sent = false;
 
-                // TODO: callback
-                // if state.on_receive {
-                //     on_receive(state.bytes_to_process);
-                // }
+                if let Some(on_receive) = state.on_receive {
+                    on_receive(&state.data[..state.bytes_to_process as usize]);
+                    state.bytes_to_process = 0;
+                    state.bytes_processed = 0;
+                }
             }
 
             // address detected (START/RESTART condition)
             if client_status.ap().bit_is_set() {
                 state.client_address = state.twi.sdata().read().bits();
-                state.bytes_to_process = 0;
-                state.bytes_processed = 0;
 
                 // host is reading
                 if client_status.dir().bit_is_set() {
-                    // TODO: callback
-                    // if state.on_request {
-                    //     state.on_request();
-                    // }
+                    if let Some(on_request) = state.on_request {
+                        let len = on_request(&mut state.response);
+                        state.response_len = len.min(I2C_BUFFER_SIZE) as u8;
+                    }
 
                     // response based on whether there is data to send
-                    response = if state.bytes_to_process == 0 {
+                    state.bytes_transmitted = 0;
+                    state.client_check_nak = false;
+                    response = if state.response_len == 0 {
                         Response::NakComplete
                     } else {
                         Response::AckContinue
                     };
                 }
-                // host is writing
-                else {
+                // host is writing, NAK while the last write hasn't been read yet so
+                // back to back writes aren't dropped, the host retries instead
+                else if state.bytes_processed < state.bytes_to_process {
+                    response = Response::NakComplete;
+                } else {
+                    state.bytes_to_process = 0;
+                    state.bytes_processed = 0;
                     state.host_data_sent = true;
                     response = Response::AckContinue;
                 }
             }
             // STOP condition detected
             else {
-                // TODO: callback
-                // state.bytes_to_process = 0;
-                // state.bytes_processed = 0;
                 response = Response::AckComplete;
             }
         }
@@ -530,24 +1095,21 @@ fn TWI0_TWIS() {
                 let collision = client_status.coll().bit_is_set();
                 if nak || collision {
                     state.client_check_nak = false;
-                    state.bytes_to_process = 0;
                     response = Response::AckComplete;
                 }
                 // data ACKed, continue sending
                 else {
-                    state.bytes_transmitted += 1;
                     state.client_check_nak = true;
 
                     // send more data
-                    if state.bytes_processed < state.bytes_to_process {
-                        let data = state.data[state.bytes_processed as usize];
+                    if state.bytes_transmitted < state.response_len {
+                        let data = state.response[state.bytes_transmitted as usize];
                         state.twi.sdata().write(|w| w.set(data));
-                        state.bytes_processed += 1;
+                        state.bytes_transmitted += 1;
                         response = Response::AckContinue;
                     }
                     // no more data to send
                     else {
-                        state.bytes_to_process = 0;
                         response = Response::AckComplete;
                     }
                 }
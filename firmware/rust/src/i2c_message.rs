@@ -0,0 +1,92 @@
+// Reassembly of messages sent over I2C in several writes. Each chunk starts with a header
+// byte: bit 7 marks the first chunk of a message, bit 6 the last one and bits 0-5 hold a
+// sequence number that goes up by one (mod 64) with every chunk. A message that fits in
+// one write is a single chunk with both bits set.
+
+pub const CHUNK_FIRST: u8 = 0x80;
+pub const CHUNK_LAST: u8 = 0x40;
+pub const CHUNK_SEQUENCE_MASK: u8 = 0x3F;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum ChunkError {
+    /// Chunk without a first chunk before it
+    NotStarted,
+    /// Sequence number skipped ahead or went back, a chunk went missing
+    OutOfSequence,
+    /// Message is larger than the reassembly buffer
+    Overflow,
+}
+
+// Tracks where the next chunk goes, the message itself is assembled in a buffer passed
+// to push so the caller doesn't need a second copy of it.
+pub struct MessageAssembler {
+    len: usize,
+    // sequence number of the chunk expected next, None while waiting for a first chunk
+    next_sequence: Option<u8>,
+}
+
+impl MessageAssembler {
+    pub const fn new() -> Self {
+        Self {
+            len: 0,
+            next_sequence: None,
+        }
+    }
+
+    #[inline(always)]
+    pub fn next_sequence(&self) -> Option<u8> {
+        self.next_sequence
+    }
+
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.next_sequence = None;
+    }
+
+    // Adds a chunk to buf, returns the length of the whole message once its last chunk is
+    // in. After an error the partial message is dropped and chunks are ignored until the
+    // next first chunk.
+    pub fn push(
+        &mut self,
+        header: u8,
+        payload: &[u8],
+        buf: &mut [u8],
+    ) -> Result<Option<usize>, ChunkError> {
+        let sequence = header & CHUNK_SEQUENCE_MASK;
+
+        // a first chunk always starts over, even in the middle of another message
+        if header & CHUNK_FIRST != 0 {
+            self.len = 0;
+            self.next_sequence = Some(sequence);
+        }
+
+        match self.next_sequence {
+            None => return Err(ChunkError::NotStarted),
+            // host retried a chunk we already have
+            Some(next) if sequence == next.wrapping_sub(1) & CHUNK_SEQUENCE_MASK => {
+                return Ok(None)
+            }
+            Some(next) if sequence != next => {
+                self.reset();
+                return Err(ChunkError::OutOfSequence);
+            }
+            _ => {}
+        }
+
+        if self.len + payload.len() > buf.len() {
+            self.reset();
+            return Err(ChunkError::Overflow);
+        }
+        buf[self.len..self.len + payload.len()].copy_from_slice(payload);
+        self.len += payload.len();
+
+        if header & CHUNK_LAST != 0 {
+            self.next_sequence = None;
+            Ok(Some(self.len))
+        } else {
+            self.next_sequence = Some((sequence + 1) & CHUNK_SEQUENCE_MASK);
+            Ok(None)
+        }
+    }
+}
@@ -4,6 +4,7 @@ const PRESS_CYCLES: u8 = 1;
 const HOLD_CYCLES: u8 = 20;
 const DEBOUNCE_CYCLES: u8 = 3;
 
+#[derive(Clone, Copy)]
 pub enum InputEvent {
     BothPressed,
     BothHeld,
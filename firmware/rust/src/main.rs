@@ -5,19 +5,28 @@
 #![feature(type_alias_impl_trait)]
 
 mod adc;
+mod battery;
+mod board;
 mod i2c;
+#[cfg(not(feature = "no_i2cutils"))]
+mod i2c_message;
 mod input;
 mod modes;
+#[cfg(feature = "music")]
+mod music;
 #[cfg(feature = "debug_panic")]
 mod panic;
 #[cfg(not(feature = "debug_panic"))]
 use panic_halt as _;
 mod random;
+#[cfg(feature = "music")]
+mod rtttl;
 mod saved_settings;
 mod tone;
 mod utils;
 
 use avrxmega_hal::eeprom::Eeprom;
+use board::{Board, Buzzer, DisplaySink};
 use avrxmega_hal::port::{mode::Output, *};
 use embedded_hal::delay::DelayNs;
 use modes::*;
@@ -59,15 +68,36 @@ type Event = input::InputEvent;
 type Setting = saved_settings::Setting;
 type SavedSettings = saved_settings::SavedSettings;
 
-// The virtual display size is larger to accomodate the physical gaps between characters.
-// The const COLUMN_GAP is the number of "empty" columns between characters and will set
-// the NUM_VIRT_COLS value, the virtual "width" of the display. During display
-// updates, specific columns are dropped/skipped to create final NUM_COLS-wide display buffer.
-const NUM_CHARS: usize = 8;
-const NUM_ROWS: usize = hcms_29xx::CHAR_HEIGHT;
-const NUM_COLS: usize = hcms_29xx::CHAR_WIDTH * NUM_CHARS;
-const NUM_VIRT_COLS: usize = NUM_COLS + (NUM_CHARS - 1) * COLUMN_GAP;
-const COLUMN_GAP: usize = 2;
+// the NONIK0 badge itself, see board.rs
+struct Nonik0;
+
+impl Board for Nonik0 {
+    type Display = Display;
+    type Buzzer = tone::Tone;
+    type Adc = adc::Adc;
+    #[cfg(not(feature = "no_i2cutils"))]
+    type I2c = i2c::I2c;
+}
+
+impl DisplaySink for Display {
+    type Error = hcms_29xx::Hcms29xxError<core::convert::Infallible>;
+
+    fn print_ascii_bytes(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        Display::print_ascii_bytes(self, bytes)
+    }
+
+    fn print_cols(&mut self, cols: &[u8]) -> Result<(), Self::Error> {
+        Display::print_cols(self, cols)
+    }
+
+    fn set_brightness(&mut self, brightness: u8) -> Result<(), Self::Error> {
+        Display::set_brightness(self, brightness)
+    }
+
+    fn set_peak_current(&mut self, peak_current: DisplayPeakCurrent) -> Result<(), Self::Error> {
+        Display::set_peak_current(self, peak_current)
+    }
+}
 
 const BASE_DELAY_MS: u32 = 5;
 
@@ -92,6 +122,7 @@ fn main() -> ! {
 
     let eeprom = Eeprom::new(dp.NVMCTRL);
     let settings = saved_settings::SavedSettings::new(eeprom);
+    adc.settings = adc::AdcSettings::new_with_settings(&settings);
     let buzzer = tone::Tone::new(dp.TCB0, pins.pa5.into_output());
    
     let mut display = Display::new(
@@ -111,7 +142,7 @@ fn main() -> ! {
     display.display_unblank().unwrap();
 
     let mut context = Context::new(settings);
-    let mut peripherals = Peripherals::new(
+    let mut peripherals = Peripherals::<Nonik0>::new(
         adc,
         buzzer,
         display,
@@ -122,8 +153,11 @@ fn main() -> ! {
     );
 
     // TODO: improve, apply saved display settings
-    let settings = Settings::new_with_settings(&context.settings);
-    settings.apply(&mut peripherals.display);
+    #[cfg(not(feature = "no_settings"))]
+    {
+        let settings = Settings::new_with_settings(&context.settings);
+        settings.apply(&mut peripherals.display);
+    }
 
     // initialize default/saved mode
     let mut mode = Mode::from_context(&context);
@@ -140,13 +174,13 @@ fn main() -> ! {
             }
             // higher/shorter tone on button press
             Some(Event::LeftPressed) | Some(Event::RightPressed) => {
-                if context.tone_enabled {
+                if context.click_enabled() {
                     peripherals.buzzer.tone(5000, 5);
                 }
             }
             // lower/longer tone on button held press
             Some(Event::LeftHeld) | Some(Event::RightHeld) => {
-                if context.tone_enabled {
+                if context.click_enabled() {
                     peripherals.buzzer.tone(4000, 10);
                 }
             }
@@ -161,6 +195,14 @@ fn main() -> ! {
 
         mode.update(&event, &mut context, &mut peripherals);
 
+        // check the coin cell now and then, warns when it's nearly flat
+        context.battery_tick(&mut peripherals, mode.holds_buzzer());
+
+        // advance the song playing in the background, if any, by the loop delay alone
+        // (see Sequencer for what blocking modes do to the tempo)
+        #[cfg(feature = "music")]
+        context.music.tick(&mut peripherals.buzzer, BASE_DELAY_MS as u16);
+
         delay.delay_ms(BASE_DELAY_MS);
     }
 }
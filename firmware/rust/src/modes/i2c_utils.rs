@@ -1,21 +1,190 @@
 #![allow(dead_code)]
 
-use super::ModeHandler;
+use super::{ModeHandler, NUM_MODES};
+#[cfg(not(feature = "no_settings"))]
+use super::{Settings, BRIGHTNESS_MAX, CURRENT_MAX};
+#[cfg(feature = "music")]
+use crate::rtttl::rtttl_name;
 use crate::{
-    i2c::{Direction, Error, I2c, I2C_BUS_SPEED, I2C_BUFFER_SIZE},
+    adc::{AdcChannel, AdcSettings},
+    board::{AnalogSource, Board, DisplaySink, I2cBus},
+    i2c::{
+        BusSpeed, Direction, Error, SniffEvent, I2C_BUFFER_SIZE, I2C_BUS_SPEED, I2C_RISE_TIME_NS,
+    },
+    i2c_message::{MessageAssembler, CHUNK_FIRST},
     utils::*,
-    Context, Event, Peripherals, SavedSettings, Setting, NUM_CHARS,
+    Context, Event, Peripherals, SavedSettings, Setting, NUM_CHARS, NUM_COLS,
 };
 
 const I2C_CLIENT_ADDRESS: u8 = 0x13;
+// client addresses that can be picked, the rest are reserved by the I2C spec
+const I2C_CLIENT_MIN_ADDRESS: u8 = 0x08;
+const I2C_CLIENT_MAX_ADDRESS: u8 = 0x77;
 const I2C_MIN_ADDRESS: u8 = 0x02;
 const I2C_MAX_ADDRESS: u8 = 0x7F;
-const MAX_MESSAGE_SIZE: usize = I2C_BUFFER_SIZE;
+const MAX_MESSAGE_SIZE: usize = 128;
+const PEEK_BLINK_PERIOD: u8 = 0x40;
+const BUS_MAP_SIZE: usize = (I2C_MAX_ADDRESS as usize + 1) / 8;
+// bus map values for the summary
+const BUS_MAP_READ: u8 = 0x01;
+const BUS_MAP_WRITE: u8 = 0x02;
+
+const SPEED_LABELS: [&[u8]; 3] = [b"100k", b"400k", b"1M"];
+// rise time estimates to pick from, in 10ns steps like the saved setting
+const RISE_TIMES: [u8; 7] = [10, 20, 30, 35, 50, 75, 100];
+
+// idle line levels for the health check in voltage_from_raw units (10mV). Below LINE_LOW
+// a line is held low, a pulled-up line reaches the I2C input high level of 0.7*Vdd (in
+// tenths of Vdd), which is taken from the last measured Vdd like the readings themselves
+const LINE_LOW: u16 = 50;
+const LINE_HIGH_VDD10: u16 = 7;
+const LINE_SDA: u8 = 0x01;
+const LINE_SCL: u8 = 0x02;
+// SDA and SCL levels without the internal pull-ups and then with them
+const HEALTH_SAMPLES: u8 = 4;
+
+// sniffer log, the last few transactions as address << 1 | read, byte count, first bytes
+const SNIFF_RECORDS: usize = 8;
+const SNIFF_RECORD_SIZE: usize = 8;
+const SNIFF_DATA_SIZE: usize = SNIFF_RECORD_SIZE - 2;
+const SNIFF_COUNT_DROPPED: u8 = 0x80; // some of the bytes were lost
+const SNIFF_SCROLL_PERIOD: u8 = 0x40;
+
+// Common parts by address range, the first match wins where parts share an address
+const KNOWN_DEVICES: [(u8, u8, &[u8]); 20] = [
+    (0x0C, 0x0C, b"AK8963"),
+    (0x13, 0x13, b"NONIK0"),
+    (0x1D, 0x1D, b"ADXL345"),
+    (0x1E, 0x1E, b"HMC5883L"),
+    (0x20, 0x27, b"PCF8574"),
+    (0x29, 0x29, b"VL53L0X"),
+    (0x38, 0x38, b"AHT20"),
+    (0x39, 0x39, b"TSL2561"),
+    (0x3C, 0x3D, b"SSD1306"),
+    (0x40, 0x40, b"INA219"),
+    (0x44, 0x45, b"SHT3x"),
+    (0x48, 0x4B, b"ADS1115"),
+    (0x50, 0x57, b"24Cxx"),
+    (0x5A, 0x5A, b"MLX90614"),
+    (0x5C, 0x5C, b"AM2320"),
+    (0x60, 0x61, b"MCP4725"),
+    (0x68, 0x68, b"DS3231"),
+    (0x69, 0x69, b"MPU6050"),
+    (0x70, 0x70, b"HT16K33"),
+    (0x76, 0x77, b"BME280"),
+];
+
+fn known_device(address: u8) -> Option<&'static [u8]> {
+    KNOWN_DEVICES
+        .iter()
+        .find(|(first, last, _)| (*first..=*last).contains(&address))
+        .map(|(_, _, name)| *name)
+}
+
+fn to_hex(byte: u8) -> [u8; 2] {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+    [HEX[(byte >> 4) as usize], HEX[(byte & 0x0F) as usize]]
+}
+
+// major, minor, patch from Cargo.toml
+const FIRMWARE_VERSION: [u8; 3] = parse_version(env!("CARGO_PKG_VERSION").as_bytes());
+
+const fn parse_version(version: &[u8]) -> [u8; 3] {
+    let mut parts = [0u8; 3];
+    let mut part = 0;
+    let mut i = 0;
+    while i < version.len() && part < parts.len() {
+        match version[i] {
+            b'.' => part += 1,
+            c @ b'0'..=b'9' => parts[part] = parts[part] * 10 + (c - b'0'),
+            _ => break, // pre-release or build metadata
+        }
+        i += 1;
+    }
+    parts
+}
+
+// Client register map. A host writes [register, data...] to set a register, or just
+// [register] to select it, after which every read returns that register's value.
+// Registers marked saved are written to EEPROM like the Settings and Nametag modes do.
+#[derive(Clone, Copy, PartialEq)]
+#[repr(u8)]
+enum Register {
+    Display = 0x00,         // rw, 1 shows the message or frame, 0 blanks the display
+    Message = 0x01,         // rw, scrolling message text, up to MAX_MESSAGE_SIZE bytes
+    ScrollSpeed = 0x02,     // rw, 0-100
+    Brightness = 0x03,      // rw, 0-15, saved
+    Current = 0x04,         // rw, 0-3 for 4.0/6.4/9.3/12.8mA, saved
+    Tone = 0x05,            // rw, 1 enables button tones, saved
+    Name = 0x06,            // rw, NUM_CHARS bytes shown by Nametag, saved
+    Mode = 0x07,            // rw, index into MODE_NAMES, writing switches to that mode
+    FirmwareVersion = 0x08, // r, 3 bytes major, minor, patch
+    MessageChunk = 0x09,    // rw, [header, text...] chunk of a longer message, see below
+    Framebuffer = 0x0A,     // rw, [first column, columns...] shown as is instead of the message
+    Address = 0x0B,         // rw, 0x08-0x77, new client address once confirmed on the badge, saved
+    #[cfg(feature = "music")]
+    Song = 0x0C, // rw, [header, RTTTL text...] chunk of a ringtone to play, see below
+}
+
+// Reading MessageChunk gives the sequence number expected next while a message is coming
+// in, CHUNK_IDLE when ready for a first chunk and CHUNK_REJECTED after a bad chunk. Song
+// takes its chunks the same way and reads the same, a ringtone that doesn't parse is
+// rejected too. Both go through the message buffer, so one transfer at a time.
+const CHUNK_IDLE: u8 = 0x80;
+const CHUNK_REJECTED: u8 = 0xFF;
+
+impl Register {
+    fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            0x00 => Register::Display,
+            0x01 => Register::Message,
+            0x02 => Register::ScrollSpeed,
+            0x03 => Register::Brightness,
+            0x04 => Register::Current,
+            0x05 => Register::Tone,
+            0x06 => Register::Name,
+            0x07 => Register::Mode,
+            0x08 => Register::FirmwareVersion,
+            0x09 => Register::MessageChunk,
+            0x0A => Register::Framebuffer,
+            0x0B => Register::Address,
+            #[cfg(feature = "music")]
+            0x0C => Register::Song,
+            _ => return None,
+        })
+    }
+}
 
 #[derive(Clone, Copy)]
 pub enum I2CUtil {
     ScannerHost,
     MessageClient,
+    // registers of the device the scanner stopped on, not saved as it needs a fresh scan
+    RegisterPeek,
+    // every responder from one sweep of the bus
+    ScanSummary,
+    // speed and rise time used by the host utils
+    BusConfig,
+    // highest speed each device still ACKs at
+    SpeedSweep,
+    // idle levels of SDA and SCL, frees a stuck bus
+    BusHealth,
+    // log of traffic between other hosts and devices
+    Sniffer,
+    // address the message client answers on
+    ClientAddress,
+}
+
+// Health check verdict, with the lines it applies to
+#[derive(Clone, Copy)]
+enum BusHealth {
+    Ok,
+    // nothing but the internal pull-ups keeps the line high
+    NoPullUp(u8),
+    // low even with the internal pull-ups, shorted or held by a device
+    Low(u8),
+    // SDA was held low and the recovery clocks freed it
+    Recovered,
 }
 
 pub struct I2CUtils {
@@ -26,14 +195,51 @@ pub struct I2CUtils {
     scan_address: u8,
     scan_direction: Direction,
     scan_error: Option<Error>,
+    // register peek/poke data
+    peek_register: u8,
+    peek_value: Result<u8, Error>,
+    peek_edit_value: Option<u8>,
+    // 2 bits per address shared by the summary (directions that ACKed) and the speed
+    // sweep (highest speed that ACKed + 1), 0 for no device
+    bus_map: [[u8; BUS_MAP_SIZE]; 2],
+    summary_address: u8,
+    // bus config data
+    bus_speed: BusSpeed,
+    bus_rise_time: u8,
+    bus_rise_time_shown: bool,
+    // bus health data
+    health_levels: [[u16; 2]; 2],
+    health_sample: u8,
+    health_result: Option<BusHealth>,
+    // the user's ADC settings while the check runs with its own
+    health_adc_settings: Option<AdcSettings>,
+    // sniffer data
+    sniff_log: [[u8; SNIFF_RECORD_SIZE]; SNIFF_RECORDS],
+    sniff_next: u8,
+    sniff_count: u8,
+    // records back from the newest, 0 follows new traffic
+    sniff_view: u8,
+    sniff_scroll: u8,
+    // ACK writes to log their bytes, off so the sniffer doesn't drive the bus
+    sniff_ack: bool,
     // message data
-    //msg_buf: [u8; MAX_MESSAGE_SIZE],
+    msg_assembler: MessageAssembler,
+    msg_chunk_error: bool,
     msg_data: [u8; MAX_MESSAGE_SIZE],
     msg_display: bool,
     msg_len: u8,
-    //msg_buf_pos: u8,
     msg_scroll_pos: u8,
     msg_speed: u8,
+    // raw columns from the host, shown instead of the message once written
+    frame: [u8; NUM_COLS],
+    frame_display: bool,
+    frame_offset: u8,
+    // client registers
+    client_register: Register,
+    client_response_stale: bool,
+    client_address: u8,
+    // address written by the host, waiting to be confirmed on the badge
+    client_address_pending: Option<u8>,
     // for display timing
     counter: u8,
 }
@@ -42,9 +248,26 @@ impl I2CUtils {
     pub fn new_with_settings(settings: &SavedSettings) -> Self {
         let saved_util = match settings.read_setting_byte(Setting::I2CPage) {
             0 => I2CUtil::ScannerHost,
+            3 => I2CUtil::ScanSummary,
+            4 => I2CUtil::BusConfig,
+            5 => I2CUtil::SpeedSweep,
+            6 => I2CUtil::BusHealth,
+            7 => I2CUtil::Sniffer,
+            8 => I2CUtil::ClientAddress,
             _ => I2CUtil::MessageClient,
         };
 
+        let bus_speed = BusSpeed::from_u8(settings.read_setting_byte(Setting::I2CSpeed))
+            .unwrap_or(I2C_BUS_SPEED);
+        let mut bus_rise_time = settings.read_setting_byte(Setting::I2CRiseTime);
+        if !RISE_TIMES.contains(&bus_rise_time) {
+            bus_rise_time = (I2C_RISE_TIME_NS / 10) as u8;
+        }
+        let mut client_address = settings.read_setting_byte(Setting::I2CAddress);
+        if !(I2C_CLIENT_MIN_ADDRESS..=I2C_CLIENT_MAX_ADDRESS).contains(&client_address) {
+            client_address = I2C_CLIENT_ADDRESS;
+        }
+
         let mut msg_data = [0u8; MAX_MESSAGE_SIZE];
         let msg = b"Waiting for message...";
         let len = msg_data.len().min(msg.len());
@@ -58,28 +281,57 @@ impl I2CUtils {
             scan_address: I2C_MIN_ADDRESS - 1,
             scan_direction: Direction::Write,
             scan_error: None,
-            //msg_buf: [0; MAX_MESSAGE_SIZE],
+            peek_register: 0,
+            peek_value: Err(Error::Uninit),
+            peek_edit_value: None,
+            bus_map: [[0; BUS_MAP_SIZE]; 2],
+            summary_address: 0,
+            bus_speed,
+            bus_rise_time,
+            bus_rise_time_shown: false,
+            health_levels: [[0; 2]; 2],
+            health_sample: 0,
+            health_result: None,
+            health_adc_settings: None,
+            sniff_log: [[0; SNIFF_RECORD_SIZE]; SNIFF_RECORDS],
+            sniff_next: 0,
+            sniff_count: 0,
+            sniff_view: 0,
+            sniff_scroll: 0,
+            sniff_ack: false,
+            msg_assembler: MessageAssembler::new(),
+            msg_chunk_error: false,
             msg_data,
             msg_display: true,
             msg_len: len as u8,
-            //msg_buf_pos: 0,
             msg_scroll_pos: 0,
             msg_speed: 93,
+            frame: [0; NUM_COLS],
+            frame_display: false,
+            frame_offset: 0,
+            client_register: Register::Message,
+            client_response_stale: true,
+            client_address,
+            client_address_pending: None,
             counter: 0,
         }
     }
 
-    fn scan_init(&mut self, i2c: &mut I2c) {
+    fn scan_init(&mut self, i2c: &mut impl I2cBus) {
         self.scan_address = I2C_MIN_ADDRESS - 1; // reset to first address
         self.scan_direction = Direction::Write;
         self.scan_error = None;
         self.scan_found_address = 0;
 
-        i2c.host_setup(I2C_BUS_SPEED);
+        self.bus_setup(i2c, self.bus_speed);
+    }
+
+    fn bus_setup(&self, i2c: &mut impl I2cBus, speed: BusSpeed) {
+        i2c.host_setup(speed.hz(), self.bus_rise_time as u16 * 10);
     }
 
     #[inline(never)]
-    fn scan_update(&mut self, i2c: &mut I2c) -> bool {
+    fn scan_update(&mut self, i2c: &mut impl I2cBus) -> bool {
         // detect pause state
         if self.scan_found_address != 0 || self.scan_error.is_some() {
             return false;
@@ -114,98 +366,593 @@ impl I2CUtils {
     }
 
     fn format_scan_result(&self, buf: &mut [u8; NUM_CHARS]) {
-        const HEX: &[u8; 16] = b"0123456789ABCDEF";
-        fn addr_to_ascii(addr: u8) -> [u8; 2] {
-            [HEX[(addr >> 4) as usize], HEX[(addr & 0x0F) as usize]]
-        }
         if let Some(error) = self.scan_error {
             if self.counter < 0x7F {
-                format_buf(buf, b"ERR:0x", &addr_to_ascii(self.scan_address));
+                format_buf(buf, b"ERR:0x", &to_hex(self.scan_address));
             } else {
                 format_uint(buf, b"ERR:", error as u16, 0, None);
             }
         } else if self.scan_found_address > 0 {
-            format_buf(buf, b"ACK:0x", &addr_to_ascii(self.scan_found_address));
+            format_buf(buf, b"ACK:0x", &to_hex(self.scan_found_address));
         } else {
-            format_buf(buf, b"NAK:0x", &addr_to_ascii(self.scan_address));
+            format_buf(buf, b"NAK:0x", &to_hex(self.scan_address));
         }
     }
 
-    fn scroll_msg_init(&mut self, i2c: &mut I2c) {
-        self.msg_scroll_pos = 0;
-        //self.msg_buf_pos = 0;
-        i2c.client_setup(I2C_CLIENT_ADDRESS);
+    fn bus_map_get(&self, address: u8) -> u8 {
+        let (byte, bit) = ((address / 8) as usize, address % 8);
+        ((self.bus_map[0][byte] >> bit) & 1) | (((self.bus_map[1][byte] >> bit) & 1) << 1)
     }
 
-    fn scroll_msg_update(&mut self, i2c: &mut I2c) -> bool {
-        let mut update = false;
+    fn bus_map_set(&mut self, address: u8, value: u8) {
+        let (byte, bit) = ((address / 8) as usize, address % 8);
+        for (plane, map) in self.bus_map.iter_mut().enumerate() {
+            map[byte] = (map[byte] & !(1 << bit)) | (((value >> plane) & 1) << bit);
+        }
+    }
 
-        if i2c.client_available() > 0 {
-            let command = i2c.client_read().unwrap();
+    // pings every address both ways in one go, stops early on bus errors
+    fn summary_init(&mut self, i2c: &mut impl I2cBus) {
+        self.scan_init(i2c);
+        self.bus_map = [[0; BUS_MAP_SIZE]; 2];
 
-            // setDisplay
-            if command == 0x00 {
-                self.msg_display = i2c.client_read().unwrap_or(self.msg_display as u8) != 0;
-                update = true;
+        for address in I2C_MIN_ADDRESS..=I2C_MAX_ADDRESS {
+            let mut value = 0;
+            for (direction, flag) in [
+                (Direction::Write, BUS_MAP_WRITE),
+                (Direction::Read, BUS_MAP_READ),
+            ] {
+                match i2c.host_ping_device(address, direction) {
+                    Ok(false) => {}
+                    Ok(true) => value |= flag,
+                    Err(e) => {
+                        self.scan_address = address;
+                        self.scan_error = Some(e);
+                        return;
+                    }
+                }
             }
-            // setMessage
-            else if command == 0x01 {
-                // read all received data into buffer, discard extra bytes if filled
-                self.msg_len = 0;
-                while let Some(data) = i2c.client_read() {
-                    if self.msg_len < MAX_MESSAGE_SIZE as u8 {
-                        self.msg_data[self.msg_len as usize] = data;
-                        self.msg_len += 1;
+            self.bus_map_set(address, value);
+        }
+
+        self.summary_address = self.summary_next(I2C_MIN_ADDRESS - 1, true).unwrap_or(0);
+    }
+
+    // pings every address at each speed from slowest to fastest, keeping the fastest that
+    // still ACKed, then goes back to the configured speed
+    fn sweep_init(&mut self, i2c: &mut impl I2cBus) {
+        self.scan_init(i2c);
+        self.bus_map = [[0; BUS_MAP_SIZE]; 2];
+
+        'sweep: for speed in BusSpeed::ALL {
+            self.bus_setup(i2c, speed);
+            for address in I2C_MIN_ADDRESS..=I2C_MAX_ADDRESS {
+                match i2c.host_ping_device(address, Direction::Write) {
+                    Ok(false) => {}
+                    Ok(true) => self.bus_map_set(address, speed as u8 + 1),
+                    Err(e) => {
+                        self.scan_address = address;
+                        self.scan_error = Some(e);
+                        break 'sweep;
                     }
                 }
+            }
+        }
+
+        self.bus_setup(i2c, self.bus_speed);
+        self.summary_address = self.summary_next(I2C_MIN_ADDRESS - 1, true).unwrap_or(0);
+    }
+
+    // "3C  400k"
+    fn format_sweep(&self, buf: &mut [u8; NUM_CHARS]) {
+        if self.scan_error.is_some() {
+            return self.format_scan_result(buf);
+        }
+        if self.summary_address == 0 {
+            format_buf(buf, b"None", b"");
+            return;
+        }
+
+        let speed = self.bus_map_get(self.summary_address) - 1;
+        format_buf(
+            buf,
+            &to_hex(self.summary_address),
+            SPEED_LABELS[speed as usize],
+        );
+    }
+
+    fn health_init(&mut self, i2c: &mut impl I2cBus, adc: &mut impl AnalogSource) {
+        // idle levels first, with nothing but the bus's own pull-ups
+        i2c.host_end();
+        // the levels are compared against fixed thresholds, so measure them the same way
+        // every time instead of with whatever the Sensors page was set to
+        if self.health_adc_settings.is_none() {
+            self.health_adc_settings = Some(*adc.settings());
+        }
+        *adc.settings_mut() = AdcSettings::VDD_REFERENCED;
+        adc.apply_settings();
+        self.health_sample = 0;
+        self.health_result = None;
+    }
+
+    fn health_update(&mut self, i2c: &mut impl I2cBus, adc: &mut impl AnalogSource) -> bool {
+        if self.health_result.is_some() {
+            return self.counter & 0x7F == 0;
+        }
+
+        let (phase, line) = ((self.health_sample / 2) as usize, self.health_sample % 2);
+        let channel = if line == 0 {
+            AdcChannel::Vsda
+        } else {
+            AdcChannel::Vscl
+        };
+        // the lines alternate, so every reading switches channels: it's None until a
+        // conversion started after the switch (and after the pull-ups changed) is done
+        let Some(level) = adc.read_voltage_nonblocking(channel) else {
+            return false;
+        };
+        self.health_levels[phase][line as usize] = level;
+        self.health_sample += 1;
+
+        if self.health_sample == HEALTH_SAMPLES / 2 {
+            i2c.pins_to_pull_up();
+        } else if self.health_sample == HEALTH_SAMPLES {
+            self.health_result = Some(self.health_check(i2c, adc.vdd()));
+            self.health_end(adc);
+            i2c.host_end();
+            self.counter = 0;
+            return true;
+        }
+        false
+    }
+
+    // puts the user's ADC settings back in the registers too, the Sensors mode reads with
+    // whatever is set there
+    fn health_end(&mut self, adc: &mut impl AnalogSource) {
+        if let Some(settings) = self.health_adc_settings.take() {
+            *adc.settings_mut() = settings;
+            adc.apply_settings();
+        }
+        adc.disable();
+    }
+
+    fn health_check(&self, i2c: &mut impl I2cBus, vdd: u16) -> BusHealth {
+        // lines reading below the limit
+        let lines = |levels: &[u16; 2], limit: u16| {
+            (if levels[0] < limit { LINE_SDA } else { 0 })
+                | (if levels[1] < limit { LINE_SCL } else { 0 })
+        };
+        let low = lines(&self.health_levels[1], LINE_LOW);
+        let no_pull_up = lines(&self.health_levels[0], vdd * LINE_HIGH_VDD10 / 10);
+
+        match low {
+            // a device stopped mid-byte, clocking it out can free the bus
+            LINE_SDA if i2c.bus_recover() => BusHealth::Recovered,
+            0 if no_pull_up != 0 => BusHealth::NoPullUp(no_pull_up),
+            0 => BusHealth::Ok,
+            _ => BusHealth::Low(low),
+        }
+    }
 
-                // remove null terminator if present
-                if self.msg_data[self.msg_len as usize - 1] == b'\0' {
-                    self.msg_len -= 1;
+    // verdict, alternating with the idle levels as "b3.2y3.2" (standard SDA/SCL wire colors)
+    fn format_health(&self, buf: &mut [u8; NUM_CHARS]) {
+        let Some(result) = self.health_result else {
+            format_buf(buf, b"Checking", b"");
+            return;
+        };
+
+        if self.counter >= 0x80 {
+            let levels = self.health_levels[0].iter().zip(b"by");
+            for (part, (&level, color)) in buf.chunks_mut(NUM_CHARS / 2).zip(levels) {
+                format_uint(part, &[*color], level.min(999) / 10, 1, None);
+            }
+            return;
+        }
+
+        let (left, right): (&[u8], &[u8]) = match result {
+            BusHealth::Ok => (b"Bus OK", b""),
+            BusHealth::Recovered => (b"Unstuck", b""),
+            BusHealth::NoPullUp(LINE_SDA) => (b"SDA", b"noPU"),
+            BusHealth::NoPullUp(LINE_SCL) => (b"SCL", b"noPU"),
+            BusHealth::NoPullUp(_) => (b"No PU", b""),
+            BusHealth::Low(LINE_SDA) => (b"SDA", b"low"),
+            BusHealth::Low(LINE_SCL) => (b"SCL", b"low"),
+            BusHealth::Low(_) => (b"Both low", b""),
+        };
+        format_buf(buf, left, right);
+    }
+
+    fn sniff_init(&mut self, i2c: &mut impl I2cBus) {
+        self.sniff_next = 0;
+        self.sniff_count = 0;
+        self.sniff_view = 0;
+        self.sniff_scroll = 0;
+        i2c.sniff_setup(self.sniff_ack);
+    }
+
+    fn sniff_newest(&mut self) -> &mut [u8; SNIFF_RECORD_SIZE] {
+        let slot = (self.sniff_next as usize + SNIFF_RECORDS - 1) % SNIFF_RECORDS;
+        &mut self.sniff_log[slot]
+    }
+
+    fn sniff_update(&mut self, i2c: &mut impl I2cBus) -> bool {
+        let mut update = false;
+
+        while let Some(event) = i2c.sniff_read() {
+            match event {
+                SniffEvent::Start(address, direction) => {
+                    let record = &mut self.sniff_log[self.sniff_next as usize];
+                    *record = [0; SNIFF_RECORD_SIZE];
+                    record[0] = address << 1 | (direction == Direction::Read) as u8;
+                    self.sniff_next = (self.sniff_next + 1) % SNIFF_RECORDS as u8;
+                    self.sniff_count = (self.sniff_count + 1).min(SNIFF_RECORDS as u8);
+                    // keep showing the same record while looking back
+                    if self.sniff_view > 0 {
+                        self.sniff_view = (self.sniff_view + 1).min(self.sniff_count - 1);
+                    } else {
+                        self.sniff_scroll = 0;
+                    }
+                }
+                SniffEvent::Data(byte) if self.sniff_count > 0 => {
+                    let record = self.sniff_newest();
+                    let count = record[1] & !SNIFF_COUNT_DROPPED;
+                    if (count as usize) < SNIFF_DATA_SIZE {
+                        record[2 + count as usize] = byte;
+                    }
+                    record[1] =
+                        (count + 1).min(!SNIFF_COUNT_DROPPED) | (record[1] & SNIFF_COUNT_DROPPED);
                 }
+                SniffEvent::Dropped if self.sniff_count > 0 => {
+                    self.sniff_newest()[1] |= SNIFF_COUNT_DROPPED;
+                }
+                _ => {}
+            }
+            update = true;
+        }
 
-                self.msg_scroll_pos = 0;
+        // move through the logged bytes two at a time on the display
+        if self.counter % SNIFF_SCROLL_PERIOD == 0 {
+            let stored = self.sniff_stored(self.sniff_viewed());
+            if stored > 2 {
+                self.sniff_scroll = (self.sniff_scroll + 1) % (stored - 1);
                 update = true;
+            }
+        }
+
+        update
+    }
+
+    fn sniff_viewed(&self) -> &[u8; SNIFF_RECORD_SIZE] {
+        let back = self.sniff_view as usize + 1;
+        &self.sniff_log[(self.sniff_next as usize + SNIFF_RECORDS - back) % SNIFF_RECORDS]
+    }
+
+    fn sniff_stored(&self, record: &[u8; SNIFF_RECORD_SIZE]) -> u8 {
+        (record[1] & !SNIFF_COUNT_DROPPED).min(SNIFF_DATA_SIZE as u8)
+    }
+
+    // "3CW:00AF", the address, R/W and the logged bytes, '!' instead of ':' if some were lost
+    fn format_sniff(&self, buf: &mut [u8; NUM_CHARS]) {
+        if self.sniff_count == 0 {
+            if self.sniff_ack {
+                format_buf(buf, b"Snif", b"+ACK");
+            } else {
+                format_buf(buf, b"Sniffing", b"");
+            }
+            return;
+        }
+
+        let record = self.sniff_viewed();
+        let stored = self.sniff_stored(record) as usize;
+        buf.fill(b' ');
+        buf[..2].copy_from_slice(&to_hex(record[0] >> 1));
+        buf[2] = if record[0] & 0x01 != 0 { b'R' } else { b'W' };
+        if stored > 0 {
+            buf[3] = if record[1] & SNIFF_COUNT_DROPPED != 0 {
+                b'!'
+            } else {
+                b':'
+            };
+        }
+        let bytes = record[2..2 + stored]
+            .iter()
+            .skip(self.sniff_scroll as usize);
+        for (i, &byte) in bytes.take(2).enumerate() {
+            buf[4 + i * 2..6 + i * 2].copy_from_slice(&to_hex(byte));
+        }
+    }
+
+    fn format_bus_config(&self, buf: &mut [u8; NUM_CHARS]) {
+        if self.bus_rise_time_shown {
+            format_uint(buf, b"Rise", self.bus_rise_time as u16 * 10, 0, None);
+        } else {
+            format_buf(buf, b"Spd", SPEED_LABELS[self.bus_speed as usize]);
+        }
+    }
 
-                // TODO: debug dropped chunks with consecutive writes from host
-                // while let Some(data) = i2c.client_read() {
-                //     if self.msg_buf_pos < MAX_MESSAGE_SIZE as u8 {
-                //         self.msg_buf[self.msg_buf_pos as usize] = data;
-                //         self.msg_buf_pos += 1;
-                //     }
-                // }
-
-                // // if buffer is full, add null terminator
-                // if self.msg_buf_pos >= MAX_MESSAGE_SIZE as u8 {
-                //     self.msg_buf[self.msg_buf_pos as usize - 1] = b'\0';
-                // }
-
-                // // last chunk if null terminator seen
-                // if self.msg_buf_pos > 0 && self.msg_buf[self.msg_buf_pos as usize - 1] == b'\0' {
-                //     // copy data without null terminator
-                //     self.msg_len = self.msg_buf_pos - 1; 
-                //     self.msg_data[..self.msg_len as usize]
-                //         .copy_from_slice(&self.msg_buf[..self.msg_len as usize]);
-
-                //     // reset positions
-                //     self.msg_buf_pos = 0;
-                //     self.msg_scroll_pos = 0;
-                //     update = true;
-                // }
-            }
-            // setScrollSpeed
-            else if command == 0x02 {
-                self.msg_speed = i2c.client_read().unwrap_or(self.msg_speed);
-                if self.msg_speed > 100 {
-                    self.msg_speed = 100;
+    // next responder after (or before) address, wrapping around
+    fn summary_next(&self, address: u8, inc: bool) -> Option<u8> {
+        let count = I2C_MAX_ADDRESS - I2C_MIN_ADDRESS + 1;
+        let mut candidate = address;
+        for _ in 0..count {
+            candidate = if inc {
+                if candidate >= I2C_MAX_ADDRESS {
+                    I2C_MIN_ADDRESS
+                } else {
+                    candidate + 1
                 }
+            } else if candidate <= I2C_MIN_ADDRESS {
+                I2C_MAX_ADDRESS
+            } else {
+                candidate - 1
+            };
+            if self.bus_map_get(candidate) != 0 {
+                return Some(candidate);
+            }
+        }
+        None
+    }
+
+    // alternates the name with the address, both are redrawn when the counter wraps
+    fn summary_update(&self) -> bool {
+        self.counter & 0x7F == 0
+    }
+
+    // "0x3C  RW" then the part name, R- or -W for devices that only ACK one direction
+    fn format_summary(&self, buf: &mut [u8; NUM_CHARS]) {
+        if self.scan_error.is_some() {
+            return self.format_scan_result(buf);
+        }
+        if self.summary_address == 0 {
+            format_buf(buf, b"None", b"");
+            return;
+        }
+
+        if self.counter < 0x80 {
+            let value = self.bus_map_get(self.summary_address);
+            let (read, write) = (value & BUS_MAP_READ != 0, value & BUS_MAP_WRITE != 0);
+            let flags = [
+                if read { b'R' } else { b'-' },
+                if write { b'W' } else { b'-' },
+            ];
+            let mut address = [b'0', b'x', 0, 0];
+            address[2..].copy_from_slice(&to_hex(self.summary_address));
+            format_buf(buf, &address, &flags);
+        } else {
+            let name = known_device(self.summary_address).unwrap_or(b"Unknown");
+            format_buf(buf, name, b"");
+        }
+    }
+
+    fn peek_read(&mut self, i2c: &mut impl I2cBus) {
+        let mut value = [0u8];
+        self.peek_value = i2c
+            .host_write_read(self.scan_found_address, &[self.peek_register], &mut value)
+            .map(|_| value[0]);
+    }
+
+    fn peek_write(&mut self, i2c: &mut impl I2cBus, value: u8) {
+        // read back to show what the device actually kept
+        match i2c.host_write(self.scan_found_address, &[self.peek_register, value]) {
+            Ok(()) => self.peek_read(i2c),
+            Err(e) => self.peek_value = Err(e),
+        }
+    }
+
+    // blinks the value while editing
+    fn peek_update(&mut self) -> bool {
+        self.peek_edit_value.is_some() && self.counter % PEEK_BLINK_PERIOD == 0
+    }
+
+    // "AA RR:VV", device address, register and value, '=' while editing the value
+    fn format_peek(&self, buf: &mut [u8; NUM_CHARS]) {
+        buf[..2].copy_from_slice(&to_hex(self.scan_found_address));
+        buf[2] = b' ';
+        buf[3..5].copy_from_slice(&to_hex(self.peek_register));
+        match (self.peek_edit_value, self.peek_value) {
+            (Some(value), _) => {
+                buf[5] = b'=';
+                buf[6..].copy_from_slice(&to_hex(value));
+                if self.counter & PEEK_BLINK_PERIOD != 0 {
+                    buf[6..].fill(b' ');
+                }
+            }
+            (None, Ok(value)) => {
+                buf[5] = b':';
+                buf[6..].copy_from_slice(&to_hex(value));
+            }
+            (None, Err(_)) => {
+                buf[5] = b':';
+                buf[6..].copy_from_slice(b"--");
+            }
+        }
+    }
+
+    fn scroll_msg_init(&mut self, i2c: &mut impl I2cBus) {
+        self.msg_scroll_pos = 0;
+        self.msg_assembler.reset();
+        self.msg_chunk_error = false;
+        self.frame_display = false;
+        self.frame_offset = 0;
+        self.client_register = Register::Message;
+        self.client_response_stale = true;
+        self.client_address_pending = None;
+        i2c.client_setup(self.client_address);
+    }
+
+    // next or previous client address, wrapping around
+    fn client_address_step(&mut self, inc: bool) {
+        self.client_address = if inc {
+            if self.client_address >= I2C_CLIENT_MAX_ADDRESS {
+                I2C_CLIENT_MIN_ADDRESS
+            } else {
+                self.client_address + 1
+            }
+        } else if self.client_address <= I2C_CLIENT_MIN_ADDRESS {
+            I2C_CLIENT_MAX_ADDRESS
+        } else {
+            self.client_address - 1
+        };
+    }
+
+    // moves the running client over to the address the host asked for
+    fn client_address_confirm(&mut self, context: &mut Context, i2c: &mut impl I2cBus) {
+        let Some(address) = self.client_address_pending.take() else {
+            return;
+        };
+        self.client_address = address;
+        context
+            .settings
+            .save_setting_byte(Setting::I2CAddress, address);
+        i2c.client_end();
+        i2c.client_setup(address);
+        self.client_response_stale = true;
+    }
+
+    // "Adr 0x13", or "0x14 ok?" while a new address from the host waits for confirmation
+    fn format_client_address(&self, buf: &mut [u8; NUM_CHARS]) {
+        let mut address = [b'0', b'x', 0, 0];
+        match self.client_address_pending {
+            Some(pending) => {
+                address[2..].copy_from_slice(&to_hex(pending));
+                format_buf(buf, &address, b"ok?");
+            }
+            None => {
+                address[2..].copy_from_slice(&to_hex(self.client_address));
+                format_buf(buf, b"Adr", &address);
+            }
+        }
+    }
+
+    fn scroll_msg_update<B: Board>(
+        &mut self,
+        context: &mut Context,
+        peripherals: &mut Peripherals<B>,
+    ) -> bool {
+        let mut update = false;
+
+        if peripherals.i2c.client_available() > 0 {
+            let register = Register::from_u8(peripherals.i2c.client_read().unwrap());
+            if let Some(register) = register {
+                self.client_register = register;
+            }
+            self.client_response_stale = true;
+
+            match register {
+                // write with no data just selects the register for reading
+                Some(Register::Message) if peripherals.i2c.client_available() > 0 => {
+                    // read all received data into buffer, discard extra bytes if filled
+                    self.msg_len = 0;
+                    while let Some(data) = peripherals.i2c.client_read() {
+                        if self.msg_len < MAX_MESSAGE_SIZE as u8 {
+                            self.msg_data[self.msg_len as usize] = data;
+                            self.msg_len += 1;
+                        }
+                    }
+
+                    // remove null terminator if present
+                    if self.msg_data[self.msg_len as usize - 1] == b'\0' {
+                        self.msg_len -= 1;
+                    }
+
+                    self.msg_scroll_pos = 0;
+                    self.frame_display = false;
+                    update = true;
+                }
+                Some(Register::MessageChunk) if peripherals.i2c.client_available() > 0 => {
+                    let header = peripherals.i2c.client_read().unwrap();
+                    update |= header & CHUNK_FIRST != 0;
+                    if let Some(mut msg_len) = self.msg_chunk_push(header, &mut peripherals.i2c) {
+                        // remove null terminator if present
+                        if msg_len > 0 && self.msg_data[msg_len - 1] == b'\0' {
+                            msg_len -= 1;
+                        }
+                        self.msg_len = msg_len as u8;
+                        self.msg_scroll_pos = 0;
+                        self.frame_display = false;
+                        update = true;
+                    }
+                }
+                // the ringtone replaces the message, which then shows the song's name
+                #[cfg(feature = "music")]
+                Some(Register::Song) if peripherals.i2c.client_available() > 0 => {
+                    let header = peripherals.i2c.client_read().unwrap();
+                    update |= header & CHUNK_FIRST != 0;
+                    if let Some(len) = self.msg_chunk_push(header, &mut peripherals.i2c) {
+                        let song = &self.msg_data[..len];
+                        if context.music.play_rtttl(song).is_ok() {
+                            self.msg_len = rtttl_name(song).len() as u8;
+                            self.msg_scroll_pos = 0;
+                            self.frame_display = false;
+                            update = true;
+                        } else {
+                            self.msg_chunk_error = true;
+                        }
+                    }
+                }
+                // [first column] alone only moves where reads start
+                Some(Register::Framebuffer) if peripherals.i2c.client_available() > 0 => {
+                    let offset = peripherals.i2c.client_read().unwrap() as usize;
+                    if offset < NUM_COLS {
+                        self.frame_offset = offset as u8;
+                        let mut col = offset;
+                        while let Some(data) = peripherals.i2c.client_read() {
+                            if col < NUM_COLS {
+                                self.frame[col] = data;
+                                col += 1;
+                            }
+                        }
+                        if col > offset {
+                            self.frame_display = true;
+                            update = true;
+                        }
+                    }
+                }
+                Some(register) => {
+                    let mut data = [0u8; NUM_CHARS];
+                    let mut len = 0;
+                    while len < NUM_CHARS {
+                        match peripherals.i2c.client_read() {
+                            Some(byte) => data[len] = byte,
+                            None => break,
+                        }
+                        len += 1;
+                    }
+
+                    if len > 0 {
+                        // leaving the mode for another one or the menu (0), the next one
+                        // takes over. I2C Util's own index keeps the client going.
+                        let index = data[0] as usize;
+                        if register == Register::Mode
+                            && index < NUM_MODES
+                            && index != context.mode_index()
+                        {
+                            peripherals.i2c.client_end();
+                            if index == 0 {
+                                context.to_menu();
+                            } else {
+                                context.to_mode(index);
+                            }
+                            return false;
+                        }
+                        update |= self.write_register(register, &data[..len], context, peripherals);
+                    }
+                }
+                None => {}
             }
 
             // flush any extra data
-            while i2c.client_read().is_some() {}
+            while peripherals.i2c.client_read().is_some() {}
         }
 
-        if self.counter > 100 - self.msg_speed {
+        // keep the selected register's value ready for the host's next read
+        if self.client_response_stale {
+            self.client_response_stale = false;
+            self.load_response(context, &mut peripherals.i2c);
+        }
+
+        // the host draws every frame itself, nothing to scroll
+        if !self.frame_display && self.counter > 100 - self.msg_speed {
             self.counter = 0;
 
             let msg_full_len = (self.msg_len as usize + NUM_CHARS) as u8; // NUM_CHARS blank padding
@@ -216,6 +963,139 @@ impl I2CUtils {
         update
     }
 
+    // Adds the chunk after header to the message buffer, returns the length of the whole
+    // text once its last chunk is in
+    fn msg_chunk_push(&mut self, header: u8, i2c: &mut impl I2cBus) -> Option<usize> {
+        let mut payload = [0u8; I2C_BUFFER_SIZE];
+        let mut len = 0;
+        while let Some(data) = i2c.client_read() {
+            if len < payload.len() {
+                payload[len] = data;
+                len += 1;
+            }
+        }
+
+        // the text is assembled in place, so hide the old message meanwhile
+        if header & CHUNK_FIRST != 0 {
+            self.msg_len = 0;
+        }
+
+        let result = self
+            .msg_assembler
+            .push(header, &payload[..len], &mut self.msg_data);
+        self.msg_chunk_error = result.is_err();
+        result.unwrap_or(None)
+    }
+
+    // returns true if the message display needs to be updated
+    #[cfg_attr(feature = "no_settings", allow(unused_variables))]
+    fn write_register<B: Board>(
+        &mut self,
+        register: Register,
+        data: &[u8],
+        context: &mut Context,
+        peripherals: &mut Peripherals<B>,
+    ) -> bool {
+        let value = data[0];
+        match register {
+            Register::Display => {
+                self.msg_display = value != 0;
+                return true;
+            }
+            Register::ScrollSpeed => {
+                self.msg_speed = value.min(100);
+            }
+            // the display settings are only checked and applied with the Settings mode built
+            #[cfg(not(feature = "no_settings"))]
+            Register::Brightness if value < BRIGHTNESS_MAX => {
+                context
+                    .settings
+                    .save_setting_byte(Setting::Brightness, value);
+                Settings::new_with_settings(&context.settings).apply(&mut peripherals.display);
+            }
+            #[cfg(not(feature = "no_settings"))]
+            Register::Current if value < CURRENT_MAX => {
+                context.settings.save_setting_byte(Setting::Current, value);
+                Settings::new_with_settings(&context.settings).apply(&mut peripherals.display);
+            }
+            Register::Tone => {
+                context.tone_enabled = value != 0;
+                context
+                    .settings
+                    .save_setting_bool(Setting::Tone, context.tone_enabled);
+            }
+            // only takes effect once confirmed, a typo could lose the badge on the bus
+            Register::Address
+                if (I2C_CLIENT_MIN_ADDRESS..=I2C_CLIENT_MAX_ADDRESS).contains(&value)
+                    && value != self.client_address =>
+            {
+                self.client_address_pending = Some(value);
+                return true;
+            }
+            Register::Name => {
+                // same characters the Nametag editor allows, shorter names are padded
+                let mut name = [b' '; NUM_CHARS];
+                name[..data.len()].copy_from_slice(data);
+                if name
+                    .iter()
+                    .all(|&byte| byte.is_ascii_alphanumeric() || byte == b' ')
+                {
+                    context.settings.save_setting(Setting::Name, &name);
+                }
+            }
+            // out of range values, read-only registers and mode changes handled by caller
+            _ => {}
+        }
+        false
+    }
+
+    fn msg_chunk_status(&self) -> u8 {
+        match self.msg_assembler.next_sequence() {
+            _ if self.msg_chunk_error => CHUNK_REJECTED,
+            Some(sequence) => sequence,
+            None => CHUNK_IDLE,
+        }
+    }
+
+    fn load_response(&self, context: &Context, i2c: &mut impl I2cBus) {
+        let mut buf = [0u8; NUM_CHARS];
+        let response: &[u8] = match self.client_register {
+            Register::Message => &self.msg_data[..self.msg_len as usize],
+            Register::Name => {
+                context.settings.read_setting(Setting::Name, &mut buf);
+                &buf
+            }
+            Register::FirmwareVersion => &FIRMWARE_VERSION,
+            Register::Framebuffer => {
+                let offset = self.frame_offset as usize;
+                let len = (NUM_COLS - offset).min(I2C_BUFFER_SIZE);
+                &self.frame[offset..offset + len]
+            }
+            Register::MessageChunk => {
+                buf[0] = self.msg_chunk_status();
+                &buf[..1]
+            }
+            #[cfg(feature = "music")]
+            Register::Song => {
+                buf[0] = self.msg_chunk_status();
+                &buf[..1]
+            }
+            register => {
+                buf[0] = match register {
+                    Register::Display => self.msg_display as u8,
+                    Register::ScrollSpeed => self.msg_speed,
+                    Register::Brightness => context.settings.read_setting_byte(Setting::Brightness),
+                    Register::Current => context.settings.read_setting_byte(Setting::Current),
+                    Register::Tone => context.tone_enabled as u8,
+                    Register::Address => self.client_address,
+                    _ => context.mode_index() as u8,
+                };
+                &buf[..1]
+            }
+        };
+        i2c.client_write(response);
+    }
+
     fn format_scroll_msg(&self, buf: &mut [u8; NUM_CHARS]) {
         // adds padding spaces before and after message for scrolling effect
         for (display_index, buf_char) in buf.iter_mut().enumerate().take(NUM_CHARS) {
@@ -236,33 +1116,85 @@ impl I2CUtils {
 
 impl ModeHandler for I2CUtils {
     #[inline(never)]
-    fn update(
+    fn update<B: Board>(
         &mut self,
         event: &Option<Event>,
         context: &mut Context,
-        peripherals: &mut Peripherals,
+        peripherals: &mut Peripherals<B>,
     ) {
         let mut update = context.need_update();
         self.counter = self.counter.wrapping_add(1);
 
         if let Some(event) = event {
             match event {
+                // cancel the edit or go back to scanning
+                Event::LeftHeld if matches!(self.cur_util, I2CUtil::RegisterPeek) => {
+                    if self.peek_edit_value.take().is_none() {
+                        self.cur_util = I2CUtil::ScannerHost;
+                        self.scan_found_address = 0;
+                    }
+                    update = true;
+                }
                 Event::LeftHeld => {
                     // disable I2C host or client module before leaving mode
                     match self.cur_util {
-                        I2CUtil::ScannerHost => peripherals.i2c.host_end(),
-                        I2CUtil::MessageClient => peripherals.i2c.client_end(),
+                        I2CUtil::MessageClient | I2CUtil::Sniffer => peripherals.i2c.client_end(),
+                        I2CUtil::BusHealth => {
+                            self.health_end(&mut peripherals.adc);
+                            peripherals.i2c.host_end();
+                        }
+                        _ => peripherals.i2c.host_end(),
                     };
                     context.to_menu();
                     return;
                 }
+                // open the registers of the device the scanner stopped on
+                Event::RightHeld
+                    if matches!(self.cur_util, I2CUtil::ScannerHost)
+                        && self.scan_found_address != 0 =>
+                {
+                    self.cur_util = I2CUtil::RegisterPeek;
+                    self.peek_register = 0;
+                    self.peek_edit_value = None;
+                    self.peek_read(&mut peripherals.i2c);
+                    update = true;
+                }
+                // start editing the value, or write the edited one
+                Event::RightHeld if matches!(self.cur_util, I2CUtil::RegisterPeek) => {
+                    match self.peek_edit_value.take() {
+                        Some(value) => self.peek_write(&mut peripherals.i2c, value),
+                        None => {
+                            self.peek_edit_value = Some(*self.peek_value.as_ref().unwrap_or(&0));
+                        }
+                    }
+                    update = true;
+                }
                 Event::RightHeld => {
                     // disable I2C host or client module before switching current util
                     self.cur_util = match self.cur_util {
-                        I2CUtil::ScannerHost => {
+                        I2CUtil::ScannerHost | I2CUtil::RegisterPeek => {
                             peripherals.i2c.host_end();
-                            I2CUtil::MessageClient
+                            I2CUtil::ScanSummary
+                        }
+                        I2CUtil::ScanSummary => {
+                            peripherals.i2c.host_end();
+                            I2CUtil::SpeedSweep
+                        }
+                        I2CUtil::SpeedSweep => {
+                            peripherals.i2c.host_end();
+                            I2CUtil::BusConfig
+                        }
+                        I2CUtil::BusConfig => I2CUtil::BusHealth,
+                        I2CUtil::BusHealth => {
+                            self.health_end(&mut peripherals.adc);
+                            peripherals.i2c.host_end();
+                            I2CUtil::Sniffer
+                        }
+                        I2CUtil::Sniffer => {
+                            peripherals.i2c.client_end();
+                            I2CUtil::ClientAddress
                         }
+                        I2CUtil::ClientAddress => I2CUtil::MessageClient,
                         I2CUtil::MessageClient => {
                             peripherals.i2c.client_end();
                             I2CUtil::ScannerHost
@@ -274,8 +1206,103 @@ impl ModeHandler for I2CUtils {
                     update = true;
                     self.util_init = false;
                 }
-                Event::RightPressed => {
-                    // clear any paused state
+                Event::LeftReleased | Event::RightReleased
+                    if matches!(self.cur_util, I2CUtil::RegisterPeek) =>
+                {
+                    let inc = matches!(event, Event::RightReleased);
+                    let step = |value: u8| {
+                        if inc {
+                            value.wrapping_add(1)
+                        } else {
+                            value.wrapping_sub(1)
+                        }
+                    };
+                    match self.peek_edit_value {
+                        Some(value) => self.peek_edit_value = Some(step(value)),
+                        None => {
+                            self.peek_register = step(self.peek_register);
+                            self.peek_read(&mut peripherals.i2c);
+                        }
+                    }
+                    update = true;
+                }
+                Event::LeftReleased | Event::RightReleased
+                    if matches!(self.cur_util, I2CUtil::BusConfig) =>
+                {
+                    // right cycles the speed, left the rise time
+                    if matches!(event, Event::RightReleased) {
+                        let index = (self.bus_speed as usize + 1) % BusSpeed::ALL.len();
+                        self.bus_speed = BusSpeed::ALL[index];
+                        self.bus_rise_time_shown = false;
+                        context
+                            .settings
+                            .save_setting_byte(Setting::I2CSpeed, self.bus_speed as u8);
+                    } else {
+                        let index = RISE_TIMES
+                            .iter()
+                            .position(|&t| t == self.bus_rise_time)
+                            .map_or(0, |i| (i + 1) % RISE_TIMES.len());
+                        self.bus_rise_time = RISE_TIMES[index];
+                        self.bus_rise_time_shown = true;
+                        context
+                            .settings
+                            .save_setting_byte(Setting::I2CRiseTime, self.bus_rise_time);
+                    }
+                    update = true;
+                }
+                Event::LeftReleased | Event::RightReleased
+                    if matches!(self.cur_util, I2CUtil::ScanSummary | I2CUtil::SpeedSweep) =>
+                {
+                    let inc = matches!(event, Event::RightReleased);
+                    if let Some(address) = self.summary_next(self.summary_address, inc) {
+                        self.summary_address = address;
+                        // start on the address, not the name
+                        self.counter = 0;
+                        update = true;
+                    }
+                }
+                // older transaction, wrapping back to the newest
+                Event::RightReleased if matches!(self.cur_util, I2CUtil::Sniffer) => {
+                    if self.sniff_count > 0 {
+                        self.sniff_view = (self.sniff_view + 1) % self.sniff_count;
+                        self.sniff_scroll = 0;
+                        update = true;
+                    }
+                }
+                // toggle ACKing writes, starting a new log
+                Event::LeftReleased if matches!(self.cur_util, I2CUtil::Sniffer) => {
+                    self.sniff_ack = !self.sniff_ack;
+                    self.util_init = false;
+                    update = true;
+                }
+                Event::LeftReleased | Event::RightReleased
+                    if matches!(self.cur_util, I2CUtil::ClientAddress) =>
+                {
+                    self.client_address_step(matches!(event, Event::RightReleased));
+                    context
+                        .settings
+                        .save_setting_byte(Setting::I2CAddress, self.client_address);
+                    update = true;
+                }
+                // accept or reject the address the host asked for
+                Event::LeftReleased | Event::RightReleased
+                    if matches!(self.cur_util, I2CUtil::MessageClient)
+                        && self.client_address_pending.is_some() =>
+                {
+                    if matches!(event, Event::RightReleased) {
+                        self.client_address_confirm(context, &mut peripherals.i2c);
+                    } else {
+                        self.client_address_pending = None;
+                    }
+                    update = true;
+                }
+                // check again
+                Event::RightReleased if matches!(self.cur_util, I2CUtil::BusHealth) => {
+                    self.util_init = false;
+                    update = true;
+                }
+                Event::RightReleased if matches!(self.cur_util, I2CUtil::ScannerHost) => {
+                    // clear any paused state, a long press opens the device instead
                     self.scan_found_address = 0;
                     self.scan_error = None;
                 }
@@ -286,8 +1313,16 @@ impl ModeHandler for I2CUtils {
         // initialize I2C utility if not already initialized
         if !self.util_init {
             match self.cur_util {
-                I2CUtil::ScannerHost => self.scan_init(&mut peripherals.i2c),
+                I2CUtil::ScannerHost | I2CUtil::RegisterPeek => {
+                    self.scan_init(&mut peripherals.i2c)
+                }
                 I2CUtil::MessageClient => self.scroll_msg_init(&mut peripherals.i2c),
+                I2CUtil::ScanSummary => self.summary_init(&mut peripherals.i2c),
+                I2CUtil::SpeedSweep => self.sweep_init(&mut peripherals.i2c),
+                I2CUtil::BusConfig => self.bus_rise_time_shown = false,
+                I2CUtil::BusHealth => self.health_init(&mut peripherals.i2c, &mut peripherals.adc),
+                I2CUtil::Sniffer => self.sniff_init(&mut peripherals.i2c),
+                I2CUtil::ClientAddress => {}
             };
             self.util_init = true;
         }
@@ -296,18 +1331,48 @@ impl ModeHandler for I2CUtils {
         if !update {
             update = match self.cur_util {
                 I2CUtil::ScannerHost => self.scan_update(&mut peripherals.i2c),
-                I2CUtil::MessageClient => self.scroll_msg_update(&mut peripherals.i2c),
+                I2CUtil::MessageClient => self.scroll_msg_update(context, peripherals),
+                I2CUtil::RegisterPeek => self.peek_update(),
+                I2CUtil::ScanSummary => self.summary_update(),
+                I2CUtil::SpeedSweep | I2CUtil::BusConfig | I2CUtil::ClientAddress => false,
+                I2CUtil::BusHealth => {
+                    self.health_update(&mut peripherals.i2c, &mut peripherals.adc)
+                }
+                I2CUtil::Sniffer => self.sniff_update(&mut peripherals.i2c),
             };
         }
 
         if update {
-            let mut buf = [0u8; NUM_CHARS];
-            match self.cur_util {
-                I2CUtil::ScannerHost => self.format_scan_result(&mut buf),
-                I2CUtil::MessageClient => self.format_scroll_msg(&mut buf),
-            };
+            if matches!(self.cur_util, I2CUtil::MessageClient)
+                && self.frame_display
+                && self.client_address_pending.is_none()
+            {
+                let blank = [0u8; NUM_COLS];
+                let cols = if self.msg_display {
+                    &self.frame
+                } else {
+                    &blank
+                };
+                peripherals.display.print_cols(cols).unwrap();
+            } else {
+                let mut buf = [0u8; NUM_CHARS];
+                match self.cur_util {
+                    I2CUtil::ScannerHost => self.format_scan_result(&mut buf),
+                    I2CUtil::MessageClient if self.client_address_pending.is_some() => {
+                        self.format_client_address(&mut buf)
+                    }
+                    I2CUtil::MessageClient => self.format_scroll_msg(&mut buf),
+                    I2CUtil::RegisterPeek => self.format_peek(&mut buf),
+                    I2CUtil::ScanSummary => self.format_summary(&mut buf),
+                    I2CUtil::SpeedSweep => self.format_sweep(&mut buf),
+                    I2CUtil::BusConfig => self.format_bus_config(&mut buf),
+                    I2CUtil::BusHealth => self.format_health(&mut buf),
+                    I2CUtil::Sniffer => self.format_sniff(&mut buf),
+                    I2CUtil::ClientAddress => self.format_client_address(&mut buf),
+                };
 
-            peripherals.display.print_ascii_bytes(&buf).unwrap();
+                peripherals.display.print_ascii_bytes(&buf).unwrap();
+            }
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,216 @@
+use super::ModeHandler;
+use crate::{
+    board::{Board, DisplaySink},
+    impl_enum_cycle,
+    music::{PlayState, NOTES, SONGS},
+    utils::{format_buf, format_uint, EnumCycle},
+    Context, Event, Peripherals, NUM_CHARS,
+};
+
+const SCROLL_PERIOD: u8 = 30;
+const SCROLL_GAP: usize = 3;
+const TIMING_UNIT_MIN: u8 = 5;
+const TIMING_UNIT_MAX: u8 = 100;
+const OCTAVE_ADJUST_MIN: i8 = -3;
+const OCTAVE_ADJUST_MAX: i8 = 4;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Page {
+    Songs = 0,
+    Playing,
+    Tempo,
+    Octave,
+}
+
+impl_enum_cycle!(Page, 4);
+
+pub struct Jukebox {
+    cur_page: Page,
+    song_index: u8,
+    scroll_offset: u8,
+    scroll_counter: u8,
+    last_position: usize,
+    last_state: PlayState,
+}
+
+impl Jukebox {
+    pub fn new_with_context(context: &Context) -> Self {
+        // come back to the player when a song is still going in the background
+        let cur_page = if context.music.state() == PlayState::Stopped {
+            Page::Songs
+        } else {
+            Page::Playing
+        };
+
+        Jukebox {
+            cur_page,
+            song_index: 0,
+            scroll_offset: 0,
+            scroll_counter: 0,
+            last_position: 0,
+            last_state: PlayState::Stopped,
+        }
+    }
+
+    // titles longer than the display wrap around with a short gap
+    fn format_title(&self, buf: &mut [u8; NUM_CHARS]) {
+        let title = SONGS[self.song_index as usize].title;
+        if title.len() <= NUM_CHARS {
+            format_buf(buf, title, b"");
+            return;
+        }
+
+        let scroll_len = title.len() + SCROLL_GAP;
+        for (i, c) in buf.iter_mut().enumerate() {
+            let index = (self.scroll_offset as usize + i) % scroll_len;
+            *c = title.get(index).copied().unwrap_or(b' ');
+        }
+    }
+
+    fn format_playing(buf: &mut [u8; NUM_CHARS], context: &Context) {
+        match context.music.state() {
+            PlayState::Stopped => format_buf(buf, b"Stopped", b""),
+            PlayState::Paused => format_buf(buf, b"Paused", b""),
+            PlayState::Playing => {
+                // note name and octave as played, then how far into the song we are
+                let mut note = [b' '; 3];
+                if let Some((index, octave)) = context.music.current_note() {
+                    let name = NOTES[index as usize].as_bytes();
+                    note[..name.len()].copy_from_slice(name);
+                    let octave = octave as i8 + context.music.octave_adjust;
+                    note[name.len()] = b'0' + octave.clamp(0, 9) as u8;
+                }
+                format_uint(
+                    buf,
+                    &note,
+                    context.music.position().min(u16::MAX as usize) as u16,
+                    0,
+                    None,
+                );
+            }
+        }
+    }
+}
+
+impl ModeHandler for Jukebox {
+    #[inline(never)]
+    fn update<B: Board>(
+        &mut self,
+        event: &Option<Event>,
+        context: &mut Context,
+        peripherals: &mut Peripherals<B>,
+    ) {
+        let mut update = event.is_some() || context.need_update();
+
+        if let Some(event) = event {
+            match event {
+                // the song keeps playing in the background
+                Event::LeftHeld => {
+                    context.to_menu();
+                    return;
+                }
+                Event::RightHeld => {
+                    self.cur_page = self.cur_page.next_wrapping();
+                    self.scroll_offset = 0;
+                    self.scroll_counter = 0;
+                }
+                Event::LeftReleased | Event::RightReleased => {
+                    let inc = matches!(event, Event::RightReleased);
+                    match self.cur_page {
+                        Page::Songs => {
+                            let count = SONGS.len() as u8;
+                            self.song_index = if inc {
+                                (self.song_index + 1) % count
+                            } else {
+                                (self.song_index + count - 1) % count
+                            };
+                            self.scroll_offset = 0;
+                            self.scroll_counter = 0;
+                        }
+                        Page::Playing => {
+                            let music = &mut context.music;
+                            match (music.state(), inc) {
+                                (PlayState::Stopped, true) => music.play_song(self.song_index),
+                                (PlayState::Playing, true) => music.pause(&mut peripherals.buzzer),
+                                (PlayState::Paused, true) => music.resume(),
+                                (_, false) => music.stop(&mut peripherals.buzzer),
+                            }
+                        }
+                        Page::Tempo => {
+                            // a ringtone can set any unit, the buttons bring it into range
+                            let unit = &mut context.music.timing_unit_ms;
+                            *unit = if inc {
+                                unit.saturating_add(1)
+                            } else {
+                                unit.saturating_sub(1)
+                            }
+                            .clamp(TIMING_UNIT_MIN, TIMING_UNIT_MAX);
+                        }
+                        Page::Octave => {
+                            let adjust = &mut context.music.octave_adjust;
+                            *adjust = if inc {
+                                (*adjust + 1).min(OCTAVE_ADJUST_MAX)
+                            } else {
+                                (*adjust - 1).max(OCTAVE_ADJUST_MIN)
+                            };
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        match self.cur_page {
+            Page::Songs => {
+                let title_len = SONGS[self.song_index as usize].title.len();
+                if title_len > NUM_CHARS {
+                    self.scroll_counter += 1;
+                    if self.scroll_counter >= SCROLL_PERIOD {
+                        self.scroll_counter = 0;
+                        let scroll_len = title_len + SCROLL_GAP;
+                        self.scroll_offset = ((self.scroll_offset as usize + 1) % scroll_len) as u8;
+                        update = true;
+                    }
+                }
+            }
+            Page::Playing => {
+                // redraw on every new note and when a song ends on its own
+                let position = context.music.position();
+                let state = context.music.state();
+                if position != self.last_position || state != self.last_state {
+                    self.last_position = position;
+                    self.last_state = state;
+                    update = true;
+                }
+            }
+            _ => {}
+        }
+
+        if update {
+            let mut buffer = [0u8; NUM_CHARS];
+            match self.cur_page {
+                Page::Songs => self.format_title(&mut buffer),
+                Page::Playing => Self::format_playing(&mut buffer, context),
+                Page::Tempo => {
+                    format_uint(
+                        &mut buffer,
+                        b"Beat:",
+                        context.music.timing_unit_ms as u16,
+                        0,
+                        None,
+                    );
+                }
+                Page::Octave => {
+                    let adjust = context.music.octave_adjust;
+                    let sign = if adjust < 0 { b'-' } else { b'+' };
+                    format_buf(
+                        &mut buffer,
+                        b"Octave",
+                        &[sign, b'0' + adjust.unsigned_abs()],
+                    );
+                }
+            }
+            peripherals.display.print_ascii_bytes(&buffer).unwrap();
+        }
+    }
+}
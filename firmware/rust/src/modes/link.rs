@@ -0,0 +1,245 @@
+use super::ModeHandler;
+use crate::{
+    board::{Board, DisplaySink, I2cBus},
+    i2c::{I2C_BUS_SPEED, I2C_RISE_TIME_NS},
+    saved_settings::MAX_CONTACTS,
+    utils::*,
+    Context, Event, Peripherals, Rand, SavedSettings, Setting, NUM_CHARS,
+};
+use random_trait::Random as _;
+
+// Two badges on one Qwiic cable both listen as a client at LINK_ADDRESS and now and then
+// call that address as a host instead, each after its own random wait. The first to call
+// while the other is listening becomes the host of the exchange: in one transaction it
+// writes [LINK_HELLO, name...] and reads [LINK_ID, name...] back.
+const LINK_ADDRESS: u8 = 0x2B;
+const LINK_HELLO: u8 = 0x01;
+const LINK_ID: u8 = 0xA7;
+// ticks of listening before calling, long next to a call so one side is usually listening
+const LISTEN_MIN_TICKS: u8 = 20;
+const LISTEN_RANGE_TICKS: u8 = 80;
+const LINKING_PERIOD: u8 = 0x20;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Page {
+    Link,
+    Contacts,
+}
+
+pub struct Link {
+    cur_page: Page,
+    page_init: bool,
+    name: [u8; NUM_CHARS],
+    // name of the badge on the other end once linked
+    peer: Option<[u8; NUM_CHARS]>,
+    listen_ticks: u8,
+    // contacts are browsed newest first
+    contact_index: u8,
+    contact_count: u8,
+    counter: u8,
+}
+
+// same characters the Nametag editor allows
+fn valid_name(name: &[u8]) -> bool {
+    name.iter()
+        .all(|&byte| byte.is_ascii_alphanumeric() || byte == b' ')
+}
+
+impl Link {
+    pub fn new_with_settings(settings: &SavedSettings) -> Self {
+        let mut name = [0; NUM_CHARS];
+        settings.read_setting(Setting::Name, &mut name);
+        if !valid_name(&name) {
+            name.copy_from_slice(b" NONIK0 ");
+        }
+
+        Link {
+            cur_page: Page::Link,
+            page_init: false,
+            name,
+            peer: None,
+            listen_ticks: 0,
+            contact_index: 0,
+            contact_count: 0,
+            counter: 0,
+        }
+    }
+
+    fn listen(&mut self, i2c: &mut impl I2cBus) {
+        let mut response = [LINK_ID; NUM_CHARS + 1];
+        response[1..].copy_from_slice(&self.name);
+        i2c.client_setup(LINK_ADDRESS);
+        i2c.client_write(&response);
+        self.listen_ticks = LISTEN_MIN_TICKS + Rand.get_u8() % LISTEN_RANGE_TICKS;
+    }
+
+    fn link_init(&mut self, i2c: &mut impl I2cBus) {
+        self.peer = None;
+        self.counter = 0;
+        self.listen(i2c);
+    }
+
+    // another badge called and sent its name, ours is already waiting for its read
+    fn answer(&mut self, context: &mut Context, i2c: &mut impl I2cBus) -> bool {
+        let mut update = false;
+        let mut peer = [0; NUM_CHARS];
+        if i2c.client_read() == Some(LINK_HELLO) && i2c.client_available() == NUM_CHARS as u8 {
+            for byte in peer.iter_mut() {
+                *byte = i2c.client_read().unwrap();
+            }
+            update = self.linked(context, peer);
+        }
+
+        // flush any extra data
+        while i2c.client_read().is_some() {}
+        update
+    }
+
+    fn call(&mut self, context: &mut Context, i2c: &mut impl I2cBus) -> bool {
+        let mut hello = [LINK_HELLO; NUM_CHARS + 1];
+        hello[1..].copy_from_slice(&self.name);
+        let mut response = [0; NUM_CHARS + 1];
+
+        i2c.client_end();
+        i2c.host_setup(I2C_BUS_SPEED.hz(), I2C_RISE_TIME_NS);
+        let result = i2c.host_write_read(LINK_ADDRESS, &hello, &mut response);
+        i2c.host_end();
+        self.listen(i2c);
+
+        // nobody listening (or calling at the same time), try again after the next wait
+        match result {
+            Ok(()) if response[0] == LINK_ID => {
+                let mut peer = [0; NUM_CHARS];
+                peer.copy_from_slice(&response[1..]);
+                self.linked(context, peer)
+            }
+            _ => false,
+        }
+    }
+
+    fn linked(&mut self, context: &mut Context, peer: [u8; NUM_CHARS]) -> bool {
+        if !valid_name(&peer) || self.peer == Some(peer) {
+            return false;
+        }
+        context.settings.save_contact(&peer);
+        self.peer = Some(peer);
+        true
+    }
+
+    fn link_update(&mut self, context: &mut Context, i2c: &mut impl I2cBus) -> bool {
+        if i2c.client_available() > 0 {
+            return self.answer(context, i2c);
+        }
+
+        // keep answering once linked, the host may not have read our name yet
+        if self.peer.is_some() {
+            return false;
+        }
+        self.listen_ticks = self.listen_ticks.saturating_sub(1);
+        if self.listen_ticks == 0 && self.call(context, i2c) {
+            return true;
+        }
+        self.counter % LINKING_PERIOD == 0
+    }
+
+    // "Linking" with dots counting up while waiting, then the other badge's name
+    fn format_link(&self, buf: &mut [u8; NUM_CHARS]) {
+        match self.peer {
+            Some(peer) => buf.copy_from_slice(&peer),
+            None => {
+                let dots = (self.counter / LINKING_PERIOD) % 2;
+                format_buf(buf, b"Linking", &b"."[..dots as usize]);
+            }
+        }
+    }
+
+    fn contacts_init(&mut self, settings: &SavedSettings) {
+        let mut buf = [0; NUM_CHARS];
+        self.contact_count = (0..MAX_CONTACTS)
+            .take_while(|&index| settings.read_contact(index, &mut buf))
+            .count() as u8;
+        self.contact_index = 0;
+    }
+
+    fn format_contact(&self, settings: &SavedSettings, buf: &mut [u8; NUM_CHARS]) {
+        if self.contact_count == 0 {
+            format_buf(buf, b"No names", b"");
+            return;
+        }
+        settings.read_contact(self.contact_index as usize, buf);
+        if !valid_name(buf) {
+            format_buf(buf, b"???", b"");
+        }
+    }
+}
+
+impl ModeHandler for Link {
+    #[inline(never)]
+    fn update<B: Board>(
+        &mut self,
+        event: &Option<Event>,
+        context: &mut Context,
+        peripherals: &mut Peripherals<B>,
+    ) {
+        let mut update = context.need_update();
+        self.counter = self.counter.wrapping_add(1);
+
+        if let Some(event) = event {
+            match event {
+                Event::LeftHeld => {
+                    peripherals.i2c.client_end();
+                    context.to_menu();
+                    return;
+                }
+                Event::RightHeld => {
+                    self.cur_page = match self.cur_page {
+                        Page::Link => {
+                            peripherals.i2c.client_end();
+                            Page::Contacts
+                        }
+                        Page::Contacts => Page::Link,
+                    };
+                    self.page_init = false;
+                    update = true;
+                }
+                // link again, with the same or another badge
+                Event::RightReleased if self.cur_page == Page::Link && self.peer.is_some() => {
+                    self.page_init = false;
+                    update = true;
+                }
+                Event::LeftReleased | Event::RightReleased
+                    if self.cur_page == Page::Contacts && self.contact_count > 0 =>
+                {
+                    self.contact_index = if matches!(event, Event::RightReleased) {
+                        (self.contact_index + 1) % self.contact_count
+                    } else {
+                        (self.contact_index + self.contact_count - 1) % self.contact_count
+                    };
+                    update = true;
+                }
+                _ => {}
+            }
+        }
+
+        if !self.page_init {
+            match self.cur_page {
+                Page::Link => self.link_init(&mut peripherals.i2c),
+                Page::Contacts => self.contacts_init(&context.settings),
+            }
+            self.page_init = true;
+        }
+
+        if self.cur_page == Page::Link {
+            update |= self.link_update(context, &mut peripherals.i2c);
+        }
+
+        if update {
+            let mut buf = [b' '; NUM_CHARS];
+            match self.cur_page {
+                Page::Link => self.format_link(&mut buf),
+                Page::Contacts => self.format_contact(&context.settings, &mut buf),
+            }
+            peripherals.display.print_ascii_bytes(&buf).unwrap();
+        }
+    }
+}
@@ -1,5 +1,8 @@
 use super::{ModeHandler, MODE_NAMES, NUM_MODES};
-use crate::{Context, Event, Peripherals, SavedSettings, Setting};
+use crate::{
+    board::{Board, DisplaySink},
+    Context, Event, Peripherals, SavedSettings, Setting,
+};
 
 pub struct Menu {
     index: usize,
@@ -18,11 +21,11 @@ impl Menu {
 
 impl ModeHandler for Menu {
     #[inline(never)]
-    fn update(
+    fn update<B: Board>(
         &mut self,
         event: &Option<Event>,
         context: &mut Context,
-        peripherals: &mut Peripherals,
+        peripherals: &mut Peripherals<B>,
     ) {
         let mut update = context.need_update();
 
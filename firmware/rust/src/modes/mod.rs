@@ -1,6 +1,19 @@
-use crate::{adc::Adc, tone::Tone, Display, Event, SavedSettings, Setting, NUM_CHARS};
+use crate::{
+    board::{Board, DisplaySink},
+    Event, SavedSettings, Setting,
+};
 use enum_dispatch::enum_dispatch;
 
+// The virtual display size is larger to accomodate the physical gaps between characters.
+// The const COLUMN_GAP is the number of "empty" columns between characters and will set
+// the NUM_VIRT_COLS value, the virtual "width" of the display. During display
+// updates, specific columns are dropped/skipped to create final NUM_COLS-wide display buffer.
+pub const NUM_CHARS: usize = 8;
+pub const NUM_ROWS: usize = hcms_29xx::CHAR_HEIGHT;
+pub const NUM_COLS: usize = hcms_29xx::CHAR_WIDTH * NUM_CHARS;
+pub const NUM_VIRT_COLS: usize = NUM_COLS + (NUM_CHARS - 1) * COLUMN_GAP;
+pub const COLUMN_GAP: usize = 2;
+
 #[cfg(feature = "board_v0")]
 use avrxmega_hal::{
     port::{mode::*, Pin}
@@ -59,6 +72,10 @@ mod traffic;
 mod tunnel;
 #[cfg(not(feature = "no_vibes"))]
 mod vibes;
+#[cfg(feature = "music")]
+mod jukebox;
+#[cfg(not(feature = "no_link"))]
+mod link;
 
 pub use menu::*;
 #[cfg(not(feature = "no_nametag"))]
@@ -77,6 +94,10 @@ pub use traffic::*;
 pub use tunnel::*;
 #[cfg(not(feature = "no_vibes"))]
 pub use vibes::*;
+#[cfg(feature = "music")]
+pub use jukebox::*;
+#[cfg(not(feature = "no_link"))]
+pub use link::*;
 
 pub const NUM_MODES: usize = {
     let mut count = 1;
@@ -112,6 +133,14 @@ pub const NUM_MODES: usize = {
     {
         count += 1;
     }
+    #[cfg(feature = "music")]
+    {
+        count += 1;
+    }
+    #[cfg(not(feature = "no_link"))]
+    {
+        count += 1;
+    }
     count
 };
 
@@ -133,13 +162,21 @@ pub const MODE_NAMES: [&[u8; NUM_CHARS]; NUM_MODES] = [
     b"  Tunnel",
     #[cfg(not(feature = "no_vibes"))]
     b"   Vibes",
+    #[cfg(feature = "music")]
+    b" Jukebox",
+    #[cfg(not(feature = "no_link"))]
+    b"    Link",
 ];
 
 pub struct Context {
     mode_init: bool,
     mode_index: u8,
     pub tone_enabled: bool,
+    pub battery: crate::battery::Battery,
+    battery_frame_ticks: u8,
     pub settings: SavedSettings,
+    #[cfg(feature = "music")]
+    pub music: crate::music::Sequencer,
 }
 
 impl Context {
@@ -152,7 +189,11 @@ impl Context {
             mode_init: false,
             mode_index: saved_index,
             tone_enabled: settings.read_setting_bool(Setting::Tone),
+            battery: crate::battery::Battery::new_with_settings(&settings),
+            battery_frame_ticks: 0,
             settings,
+            #[cfg(feature = "music")]
+            music: crate::music::Sequencer::new(),
         }
     }
 
@@ -161,6 +202,44 @@ impl Context {
         self.mode_index == 0
     }
 
+    // button clicks are muted while a song plays, they would cut off its notes
+    #[inline(always)]
+    pub fn click_enabled(&self) -> bool {
+        #[cfg(feature = "music")]
+        if self.music.is_playing() {
+            return false;
+        }
+        self.tone_enabled
+    }
+
+    // Checks the coin cell now and then and beeps when it's nearly flat. The beep follows
+    // the tone setting and is skipped while the mode holds a tone of its own, a playing
+    // song's note is cut off and restarts after it. Without the beep the warning is shown
+    // over the mode's frame instead, which is redrawn once it's gone.
+    pub fn battery_tick<B: Board>(&mut self, peripherals: &mut Peripherals<B>, buzzer_held: bool) {
+        if self.battery.tick(&mut peripherals.adc) {
+            if self.tone_enabled && !buzzer_held {
+                crate::battery::Battery::beep(&mut peripherals.buzzer);
+                #[cfg(feature = "music")]
+                self.music.hold(crate::battery::LOW_TONE_MS);
+            } else {
+                self.battery_frame_ticks = crate::battery::LOW_FRAME_TICKS;
+            }
+        }
+
+        if self.battery_frame_ticks > 0 {
+            self.battery_frame_ticks -= 1;
+            if self.battery_frame_ticks > 0 {
+                peripherals
+                    .display
+                    .print_ascii_bytes(crate::battery::LOW_FRAME)
+                    .unwrap();
+            } else {
+                self.mode_init = false;
+            }
+        }
+    }
+
     #[inline(always)]
     pub fn need_update(&mut self) -> bool {
         let update = !self.mode_init;
@@ -187,25 +266,25 @@ impl Context {
     }
 }
 
-pub struct Peripherals {
-    pub adc: Adc,
-    pub buzzer: Tone,
-    pub display: Display,
+pub struct Peripherals<B: Board> {
+    pub adc: B::Adc,
+    pub buzzer: B::Buzzer,
+    pub display: B::Display,
     #[cfg(feature = "board_v0")]
     pub ext_pin: ExtPin,
     #[cfg(not(feature = "no_i2cutils"))]
-    pub i2c: crate::i2c::I2c,
+    pub i2c: B::I2c,
 }
 
-impl Peripherals {
+impl<B: Board> Peripherals<B> {
     pub fn new(
-        adc: Adc,
-        buzzer: Tone,
-        display: Display,
+        adc: B::Adc,
+        buzzer: B::Buzzer,
+        display: B::Display,
         #[cfg(feature = "board_v0")]
         ext_pin: Pin<Input<AnyInput>, ExtPinInt>,
         #[cfg(not(feature = "no_i2cutils"))]
-        i2c: crate::i2c::I2c,
+        i2c: B::I2c,
     ) -> Self {
         Self {
             adc,
@@ -221,12 +300,17 @@ impl Peripherals {
 
 #[enum_dispatch]
 pub trait ModeHandler {
-    fn update(
+    fn update<B: Board>(
         &mut self,
         event: &Option<Event>,
         context: &mut Context,
-        peripherals: &mut Peripherals,
+        peripherals: &mut Peripherals<B>,
     );
+
+    // a tone the mode keeps on, like the continuity tone, that other beeps mustn't cut off
+    fn holds_buzzer(&self) -> bool {
+        false
+    }
 }
 
 #[enum_dispatch(ModeHandler)]
@@ -248,9 +332,15 @@ pub enum Mode {
     Tunnel(Tunnel),
     #[cfg(not(feature = "no_vibes"))]
     Vibes(Vibes),
+    #[cfg(feature = "music")]
+    Jukebox(Jukebox),
+    #[cfg(not(feature = "no_link"))]
+    Link(Link),
 }
 
 impl Mode {
+    // the last mode's increment goes unused when the modes after it are left out
+    #[allow(unused_assignments)]
     pub fn from_context(context: &Context) -> Self {
         let index = context.mode_index();
         let mut i = 0;
@@ -312,6 +402,20 @@ impl Mode {
             if index == i {
                 return Mode::Vibes(Vibes::new());
             }
+            i += 1;
+        }
+        #[cfg(feature = "music")]
+        {
+            if index == i {
+                return Mode::Jukebox(Jukebox::new_with_context(context));
+            }
+            i += 1;
+        }
+        #[cfg(not(feature = "no_link"))]
+        {
+            if index == i {
+                return Mode::Link(Link::new_with_settings(&context.settings));
+            }
         }
         panic!("Invalid mode index: {}", index);
     }
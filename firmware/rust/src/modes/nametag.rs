@@ -1,5 +1,8 @@
 use super::ModeHandler;
-use crate::{Context, Event, Peripherals, SavedSettings, Setting, NUM_CHARS};
+use crate::{
+    board::{Board, DisplaySink},
+    Context, Event, Peripherals, SavedSettings, Setting, NUM_CHARS,
+};
 
 const BLINK_PERIOD_ON: u8 = 1;
 const BLINK_PERIOD: u8 = 20;
@@ -69,11 +72,11 @@ impl Nametag {
 
 impl ModeHandler for Nametag {
     #[inline(never)]
-    fn update(
+    fn update<B: Board>(
         &mut self,
         event: &Option<Event>,
         context: &mut Context,
-        peripherals: &mut Peripherals,
+        peripherals: &mut Peripherals<B>,
     ) {
         let mut update = context.need_update();
 
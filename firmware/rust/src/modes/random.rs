@@ -1,7 +1,9 @@
 use super::ModeHandler;
 use crate::{
-    impl_enum_cycle, utils::EnumCycle, Context, Display, Event, Peripherals, Rand, SavedSettings,
-    Setting, NUM_CHARS, NUM_COLS,
+    board::{Board, DisplaySink},
+    impl_enum_cycle,
+    utils::EnumCycle,
+    Context, Event, Peripherals, Rand, SavedSettings, Setting, NUM_CHARS, NUM_COLS,
 };
 use hcms_29xx::CHAR_WIDTH;
 use random_trait::Random as _;
@@ -86,7 +88,10 @@ pub struct Random {
 
 impl Random {
     pub fn new_with_settings(settings: &SavedSettings) -> Self {
-        let saved_page = settings.read_setting_byte(Setting::RandomPage);
+        let mut saved_page = settings.read_setting_byte(Setting::RandomPage);
+        if saved_page >= Page::COUNT {
+            saved_page = 0;
+        }
         let page = Page::from_u8(saved_page);
 
         Random { cur_page: page }
@@ -108,7 +113,7 @@ impl Random {
         buf.copy_from_slice(CUISINE_RESPONSES[index as usize % CUISINE_RESPONSES.len()]);
     }
 
-    fn roll_d6_message(value: u32, display: &mut Display) {
+    fn roll_d6_message(value: u32, display: &mut impl DisplaySink) {
         let mut value = value;
         let mut col_buf = [0; NUM_COLS];
         for index in (0..NUM_CHARS).rev() {
@@ -124,11 +129,11 @@ impl Random {
 
 impl ModeHandler for Random {
     #[inline(never)]
-    fn update(
+    fn update<B: Board>(
         &mut self,
         event: &Option<Event>,
         context: &mut Context,
-        peripherals: &mut Peripherals,
+        peripherals: &mut Peripherals<B>,
     ) {
         let mut update = context.need_update();
 
@@ -1,7 +1,12 @@
 use super::ModeHandler;
+#[cfg(not(feature = "board_v0"))]
+use crate::board::I2cBus;
 use crate::{
-    adc::*, impl_enum_cycle, utils::*, Context, Event, Peripherals, SavedSettings, Setting,
-    NUM_CHARS,
+    adc::*,
+    board::{AnalogSource, Board, Buzzer, DisplaySink},
+    battery::Battery,
+    impl_enum_cycle, utils::*, Context, Event, Peripherals, SavedSettings, Setting,
+    NUM_CHARS, NUM_COLS, NUM_ROWS,
 };
 
 pub const RESOLUTION_VALUES: [u16; 2] = [10, 8]; // 2^10 - 1, 2^8 - 1
@@ -19,10 +24,26 @@ pub const CONTINUITY_YELLOW_READING_MASK: u16 = 0x0002;
 #[cfg(not(feature = "board_v0"))]
 pub const CONTINUITY_BOTH_READING_MASK: u16 = 0x0003;
 
+// Scope page graph, one column per interval scrolling in from the right, or with a trigger
+// level set, sweeps from the left that start when a reading rises through the level
+const SCOPE_PAGE: u8 = AdcChannel::COUNT + 1;
+const SCOPE_TICK_MS: u16 = 5; // main loop period
+const SCOPE_INTERVALS: [u8; 8] = [1, 2, 4, 10, 20, 40, 100, 200]; // ticks per column
+const SCOPE_SCALES: [u16; 5] = [0, 10, 20, 50, 100]; // reading units per row, 0 for auto
+const SCOPE_TRIGGER_STEP: u16 = 10;
+const SCOPE_TRIGGER_MAX: u16 = 500;
+const SCOPE_NO_SAMPLE: u16 = u16::MAX;
+
+// Battery page, Vdd measured every BATTERY_TICKS
+const BATTERY_PAGE: u8 = AdcChannel::COUNT + 2;
+const BATTERY_TICKS: u8 = 100;
+
 #[derive(Clone, Copy)]
 pub enum SensorPage {
     AdcChannel(AdcChannel),
     ContinuityTest,
+    Scope,
+    Battery,
 }
 
 impl SensorPage {
@@ -36,7 +57,9 @@ impl SensorPage {
                     SensorPage::ContinuityTest
                 }
             }
-            SensorPage::ContinuityTest => SensorPage::AdcChannel(AdcChannel::from_u8(0)),
+            SensorPage::ContinuityTest => SensorPage::Scope,
+            SensorPage::Scope => SensorPage::Battery,
+            SensorPage::Battery => SensorPage::AdcChannel(AdcChannel::from_u8(0)),
         }
     }
 }
@@ -45,6 +68,8 @@ impl From<u8> for SensorPage {
     fn from(value: u8) -> Self {
         match value {
             1..=AdcChannel::COUNT => SensorPage::AdcChannel(AdcChannel::from_u8(value - 1)),
+            SCOPE_PAGE => SensorPage::Scope,
+            BATTERY_PAGE => SensorPage::Battery,
             _ => SensorPage::ContinuityTest,
         }
     }
@@ -54,6 +79,8 @@ impl From<SensorPage> for u8 {
     fn from(value: SensorPage) -> Self {
         match value {
             SensorPage::AdcChannel(channel) => 1 + channel.to_u8(),
+            SensorPage::Scope => SCOPE_PAGE,
+            SensorPage::Battery => BATTERY_PAGE,
             _ => 0,
         }
     }
@@ -72,9 +99,268 @@ pub enum SensorSetting {
     SetAsdv,
     SampleDelay,
     SampleLength,
+    Defaults, // either button restores AdcSettings::default()
+}
+
+impl_enum_cycle!(SensorSetting, 10);
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum SensorStat {
+    Min,
+    Max,
+    Avg,
+    Count,
+}
+
+impl_enum_cycle!(SensorStat, 4);
+
+// running statistics of a channel's readings, in the units they are shown in
+#[derive(Clone, Copy, Default)]
+pub struct ChannelStats {
+    min: u16,
+    max: u16,
+    sum: u32,
+    count: u16,
+}
+
+impl ChannelStats {
+    fn add(&mut self, reading: u16) {
+        // stop at the largest count instead of wrapping the average
+        if self.count == u16::MAX {
+            return;
+        }
+        if self.count == 0 {
+            self.min = reading;
+            self.max = reading;
+        } else {
+            self.min = self.min.min(reading);
+            self.max = self.max.max(reading);
+        }
+        self.sum += reading as u32;
+        self.count += 1;
+    }
+
+    fn value(&self, stat: SensorStat) -> u16 {
+        match stat {
+            SensorStat::Min => self.min,
+            SensorStat::Max => self.max,
+            SensorStat::Avg if self.count > 0 => (self.sum / self.count as u32) as u16,
+            SensorStat::Avg => 0,
+            SensorStat::Count => self.count,
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum ScopeOption {
+    Channel,
+    TimeDiv,
+    VoltsDiv,
+    Trigger,
 }
 
-impl_enum_cycle!(SensorSetting, 9);
+impl_enum_cycle!(ScopeOption, 4);
+
+pub struct Scope {
+    channel: AdcChannel,
+    interval: u8, // index into SCOPE_INTERVALS
+    scale: u8,    // index into SCOPE_SCALES
+    trigger: Option<u16>,
+    option: Option<ScopeOption>, // None shows the graph
+    samples: [u16; NUM_COLS],
+    // next column of a triggered sweep, NUM_COLS while waiting for the trigger
+    sweep_pos: u8,
+    ticks: u8,
+    latest: Option<u16>,
+    previous: u16,
+}
+
+impl Scope {
+    fn new() -> Self {
+        Scope {
+            #[cfg(feature = "board_v0")]
+            channel: AdcChannel::Vext,
+            #[cfg(not(feature = "board_v0"))]
+            channel: AdcChannel::Vsda,
+            interval: 2,
+            scale: 0,
+            trigger: None,
+            option: None,
+            samples: [SCOPE_NO_SAMPLE; NUM_COLS],
+            sweep_pos: NUM_COLS as u8,
+            ticks: 0,
+            latest: None,
+            previous: SCOPE_NO_SAMPLE,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.samples = [SCOPE_NO_SAMPLE; NUM_COLS];
+        self.sweep_pos = NUM_COLS as u8;
+        self.ticks = 0;
+        self.latest = None;
+        self.previous = SCOPE_NO_SAMPLE;
+    }
+
+    // returns true when a column was added
+    fn update(&mut self, adc: &mut impl AnalogSource) -> bool {
+        let reading = match self.channel {
+            AdcChannel::Temp => adc.read_temp_nonblocking(false),
+            channel => adc.read_voltage_nonblocking(channel),
+        };
+        if reading.is_some() {
+            self.latest = reading;
+        }
+
+        // the ADC can be slower than the interval, the latest reading is used again then
+        self.ticks += 1;
+        if self.ticks < SCOPE_INTERVALS[self.interval as usize] {
+            return false;
+        }
+        self.ticks = 0;
+        let Some(sample) = self.latest else {
+            return false;
+        };
+        let previous = core::mem::replace(&mut self.previous, sample);
+
+        match self.trigger {
+            None => {
+                self.samples.copy_within(1.., 0);
+                self.samples[NUM_COLS - 1] = sample;
+            }
+            Some(level) => {
+                if self.sweep_pos as usize >= NUM_COLS {
+                    if previous >= level || sample < level {
+                        return false;
+                    }
+                    self.sweep_pos = 0;
+                }
+                self.samples[self.sweep_pos as usize] = sample;
+                self.sweep_pos += 1;
+            }
+        }
+        true
+    }
+
+    // line through the samples, auto scale fits the lowest and highest sample to the rows
+    fn render(&self, cols: &mut [u8; NUM_COLS]) {
+        let per_row = SCOPE_SCALES[self.scale as usize] as u32;
+        let samples = self.samples.iter().filter(|&&s| s != SCOPE_NO_SAMPLE);
+        let low = samples.clone().min().copied().unwrap_or(0) as u32;
+        let high = samples.max().copied().unwrap_or(0) as u32;
+        let max_row = NUM_ROWS as u32 - 1;
+        let row = |sample: u16| {
+            let sample = sample as u32;
+            let row = sample.checked_div(per_row).unwrap_or(if high > low {
+                (sample - low) * max_row / (high - low)
+            } else {
+                max_row / 2
+            });
+            row.min(max_row)
+        };
+
+        let mut last_row = None;
+        for (col, &sample) in cols.iter_mut().zip(&self.samples) {
+            if sample == SCOPE_NO_SAMPLE {
+                *col = 0;
+                last_row = None;
+                continue;
+            }
+            let cur_row = row(sample);
+            let (from, to) = match last_row {
+                Some(last) if last < cur_row => (last, cur_row),
+                Some(last) => (cur_row, last),
+                None => (cur_row, cur_row),
+            };
+            // bit 0 is the top row
+            *col = (from..=to).fold(0, |bits, row| bits | 1 << (max_row - row));
+            last_row = Some(cur_row);
+        }
+    }
+
+    fn format_value(&self, buf: &mut [u8; NUM_CHARS], prefix: &[u8], value: u16) {
+        match self.channel {
+            AdcChannel::Temp => format_uint(buf, prefix, value, 0, Some(b"\x98C")),
+            _ => format_uint(buf, prefix, value, Sensors::DECIMAL_PRECISION, Some(b"V")),
+        }
+    }
+
+    fn format_option(&self, option: ScopeOption, buf: &mut [u8; NUM_CHARS]) {
+        match option {
+            ScopeOption::Channel => {
+                let name: &[u8] = match self.channel {
+                    AdcChannel::Temp => b"Temp",
+                    #[cfg(not(feature = "board_v0"))]
+                    AdcChannel::Vsda => b"Vb",
+                    #[cfg(not(feature = "board_v0"))]
+                    AdcChannel::Vscl => b"Vy",
+                    #[cfg(feature = "board_v0")]
+                    AdcChannel::Vext => b"Ve",
+                    AdcChannel::Gnd => b"Vg",
+                    AdcChannel::Vref => b"Vr",
+                };
+                format_buf(buf, b"Ch:", name);
+            }
+            ScopeOption::TimeDiv => {
+                let interval_ms = SCOPE_INTERVALS[self.interval as usize] as u16 * SCOPE_TICK_MS;
+                format_uint(buf, b"T:", interval_ms, 0, Some(b"ms"));
+            }
+            ScopeOption::VoltsDiv => match SCOPE_SCALES[self.scale as usize] {
+                0 => format_buf(buf, b"Dv:", b"auto"),
+                per_row => self.format_value(buf, b"Dv:", per_row),
+            },
+            ScopeOption::Trigger => match self.trigger {
+                Some(level) => self.format_value(buf, b"Tr:", level),
+                None => format_buf(buf, b"Tr:", b"off"),
+            },
+        }
+    }
+
+    fn adjust_option(&mut self, option: ScopeOption, increment: bool) {
+        match option {
+            ScopeOption::Channel => {
+                self.channel = if increment {
+                    self.channel.next_wrapping()
+                } else {
+                    self.channel.prev_wrapping()
+                };
+                self.clear();
+            }
+            ScopeOption::TimeDiv => {
+                self.interval = if increment {
+                    (self.interval + 1).min(SCOPE_INTERVALS.len() as u8 - 1)
+                } else {
+                    self.interval.saturating_sub(1)
+                };
+            }
+            ScopeOption::VoltsDiv => {
+                self.scale = if increment {
+                    (self.scale + 1).min(SCOPE_SCALES.len() as u8 - 1)
+                } else {
+                    self.scale.saturating_sub(1)
+                };
+            }
+            // off below the lowest level
+            ScopeOption::Trigger => {
+                self.trigger = match (self.trigger, increment) {
+                    (None, true) => Some(SCOPE_TRIGGER_STEP),
+                    (None, false) => None,
+                    (Some(level), true) => {
+                        Some((level + SCOPE_TRIGGER_STEP).min(SCOPE_TRIGGER_MAX))
+                    }
+                    (Some(level), false) => {
+                        level.checked_sub(SCOPE_TRIGGER_STEP).filter(|&level| level > 0)
+                    }
+                };
+                self.clear();
+            }
+        }
+    }
+}
 
 pub struct Sensors {
     cur_page: SensorPage,
@@ -82,6 +368,13 @@ pub struct Sensors {
     port_init: bool,
     tone_active: u8, // different values for continuity channel
     settings_active: bool,
+    stats_active: bool,
+    cur_stat: SensorStat,
+    stats_hold: bool,
+    stats: [ChannelStats; AdcChannel::COUNT as usize],
+    scope: Scope,
+    battery_ticks: u8,
+    show_percent: bool,
     // for continuity, >0 is continuity, ==0 is none
     last_reading: u16,
     #[cfg(not(feature = "board_v0"))]
@@ -103,6 +396,13 @@ impl Sensors {
             port_init: false,
             tone_active: 0,
             settings_active: false,
+            stats_active: false,
+            cur_stat: SensorStat::Min,
+            stats_hold: false,
+            stats: [ChannelStats::default(); AdcChannel::COUNT as usize],
+            scope: Scope::new(),
+            battery_ticks: 0,
+            show_percent: false,
             last_reading: 0,
             #[cfg(not(feature = "board_v0"))]
             continuity_channel: false,
@@ -157,6 +457,11 @@ impl Sensors {
             SensorSetting::SampleLength => {
                 format_uint(buf, b"Slen:", adc.sample_length as u16, 0, None)
             }
+            SensorSetting::Defaults => format_buf(
+                buf,
+                b"Dflt:",
+                BOOL_STRINGS[(*adc == AdcSettings::default()) as usize],
+            ),
         }
     }
 
@@ -222,6 +527,8 @@ impl Sensors {
                 0,
                 None,
             ),
+            // drawn as a graph instead, and see format_battery
+            (SensorPage::Scope, _) | (SensorPage::Battery, _) => unreachable!(),
             #[cfg(feature = "board_v0")]
             (SensorPage::ContinuityTest, _) => {
                 format_buf(buf, b"Cx:", BOOL_STRINGS[if value > 0 { 1 } else { 0 }]);
@@ -243,6 +550,43 @@ impl Sensors {
         format_uint(buf, prefix, value, decimals, suffix);
     }
 
+    fn cur_stats(&mut self) -> Option<&mut ChannelStats> {
+        match self.cur_page {
+            SensorPage::AdcChannel(channel) => Some(&mut self.stats[channel as usize]),
+            SensorPage::ContinuityTest | SensorPage::Scope | SensorPage::Battery => None,
+        }
+    }
+
+    // "Mn:1.23V" like the reading with the statistic's name instead, '*' instead of ':' on hold
+    fn format_stat(&mut self, buf: &mut [u8; NUM_CHARS]) {
+        let stat = self.cur_stat;
+        let stats = *self.cur_stats().unwrap();
+        let value = stats.value(stat);
+        let name: &[u8; 2] = match stat {
+            SensorStat::Min => b"Mn",
+            SensorStat::Max => b"Mx",
+            SensorStat::Avg => b"Av",
+            SensorStat::Count => b"Ct",
+        };
+        if stat == SensorStat::Count {
+            format_uint(buf, name, value, 0, None);
+        } else {
+            self.format_reading(value, buf);
+            buf[..2].copy_from_slice(name);
+        }
+        buf[2] = if self.stats_hold { b'*' } else { b':' };
+    }
+
+    // "Vdd:3.52", or "LIR: 85%" for the cell chosen with a long right press
+    fn format_battery(&self, buf: &mut [u8; NUM_CHARS], battery: &Battery, vdd: u16) {
+        if self.show_percent {
+            let cell = battery.cell();
+            format_uint(buf, cell.label(), battery.percent(vdd) as u16, 0, Some(b"%"));
+        } else {
+            format_uint(buf, b"Vdd:", vdd, Self::DECIMAL_PRECISION, None);
+        }
+    }
+
     fn decrement_cur_setting(&mut self, adc: &mut AdcSettings) {
         self.adjust_cur_setting(adc, false);
     }
@@ -277,8 +621,9 @@ impl Sensors {
             SensorSetting::Prescaler => adc.prescaler = if increment { adc.prescaler.next() } else { adc.prescaler.prev() },
             SensorSetting::InitDelay => adc.init_delay = if increment { adc.init_delay.next() } else { adc.init_delay.prev() },
             SensorSetting::SetAsdv => adc.asdv = !adc.asdv,
-            SensorSetting::SampleDelay => adc.sample_delay = if increment { (adc.sample_delay + 1).min(15) } else { adc.sample_delay.saturating_sub(1) },
-            SensorSetting::SampleLength => adc.sample_length = if increment { (adc.sample_length + 1).min(31) } else { adc.sample_length.saturating_sub(1) },
+            SensorSetting::SampleDelay => adc.sample_delay = if increment { (adc.sample_delay + 1).min(SAMPLE_DELAY_MAX) } else { adc.sample_delay.saturating_sub(1) },
+            SensorSetting::SampleLength => adc.sample_length = if increment { (adc.sample_length + 1).min(SAMPLE_LENGTH_MAX) } else { adc.sample_length.saturating_sub(1) },
+            SensorSetting::Defaults => *adc = AdcSettings::default(),
         }
     }
 
@@ -294,28 +639,43 @@ impl Sensors {
                     self.show_raw = false;
                 }
             }
+            SensorPage::Battery => self.show_percent = !self.show_percent,
             _ => self.show_raw = !self.show_raw,
         }
     }
 }
 
 impl ModeHandler for Sensors {
+    fn holds_buzzer(&self) -> bool {
+        self.tone_active > 0
+    }
+
     #[inline(never)]
-    fn update(
+    fn update<B: Board>(
         &mut self,
         event: &Option<Event>,
         context: &mut Context,
-        peripherals: &mut Peripherals,
+        peripherals: &mut Peripherals<B>,
     ) {
         let mut update = context.need_update();
 
         if let Some(event) = event {
             match event {
                 Event::LeftHeld => {
-                    // in settings: exit to readings and apply settings, in readings: exit to menu
+                    // in settings: exit to readings and apply and save settings, in stats: exit to
+                    // readings, in readings: exit to menu
                     if self.settings_active {
                         self.settings_active = false;
                         peripherals.adc.apply_settings();
+                        peripherals.adc.settings().save(&mut context.settings);
+                        // readings may be in other units now
+                        self.stats = [ChannelStats::default(); AdcChannel::COUNT as usize];
+                        update = true;
+                    } else if self.stats_active {
+                        self.stats_active = false;
+                        self.stats_hold = false;
+                        update = true;
+                    } else if self.scope.option.take().is_some() {
                         update = true;
                     } else {
                         // disable ADC when leaving utils mode
@@ -331,25 +691,58 @@ impl ModeHandler for Sensors {
                     }
                 }
                 Event::RightHeld => {
-                    // toggle setting or toggle into settings
+                    // toggle setting, toggle into stats (ADC channels only) or into settings
                     if self.settings_active {
                         self.cur_setting = self.cur_setting.next_wrapping();
-                    } else {
+                    } else if matches!(self.cur_page, SensorPage::Scope) {
+                        // scope options instead of settings, they wrap around
+                        self.scope.option = Some(match self.scope.option {
+                            Some(option) => option.next_wrapping(),
+                            None => ScopeOption::Channel,
+                        });
+                    } else if matches!(self.cur_page, SensorPage::Battery) {
+                        context.battery.next_cell(&mut context.settings);
+                        self.show_percent = true;
+                    } else if self.stats_active || self.cur_stats().is_none() {
+                        self.stats_active = false;
+                        self.stats_hold = false;
                         self.settings_active = true;
+                    } else {
+                        self.stats_active = true;
                     }
                     update = true;
                 }
                 Event::LeftReleased => {
                     if self.settings_active {
-                        self.decrement_cur_setting(&mut peripherals.adc.settings);
+                        self.decrement_cur_setting(peripherals.adc.settings_mut());
+                    } else if self.stats_active {
+                        // reset from the count, hold/release from the others
+                        if self.cur_stat == SensorStat::Count {
+                            *self.cur_stats().unwrap() = ChannelStats::default();
+                            self.stats_hold = false;
+                        } else {
+                            self.stats_hold = !self.stats_hold;
+                        }
+                    } else if matches!(self.cur_page, SensorPage::Scope) {
+                        match self.scope.option {
+                            Some(option) => self.scope.adjust_option(option, false),
+                            None => self.scope.clear(),
+                        }
                     } else {
                         self.toggle_reading_format();
+                        if let Some(stats) = self.cur_stats() {
+                            *stats = ChannelStats::default();
+                        }
                     }
                     update = true;
                 }
                 Event::RightReleased => {
                     if self.settings_active {
-                        self.increment_cur_setting(&mut peripherals.adc.settings);
+                        self.increment_cur_setting(peripherals.adc.settings_mut());
+                    } else if self.stats_active {
+                        self.cur_stat = self.cur_stat.next_wrapping();
+                    } else if let Some(option) = self.scope.option {
+                        self.scope.adjust_option(option, true);
                     } else {
                         self.cur_page = self.cur_page.next();
                         self.port_init = false;
@@ -370,14 +763,20 @@ impl ModeHandler for Sensors {
         if !self.port_init {
             match self.cur_page {
                 #[cfg(feature = "board_v0")] 
-                SensorPage::AdcChannel(_) => _ = peripherals.ext_pin.to_floating(),
+                SensorPage::AdcChannel(_) | SensorPage::Scope | SensorPage::Battery => {
+                    _ = peripherals.ext_pin.to_floating()
+                }
                 #[cfg(feature = "board_v0")] 
                 SensorPage::ContinuityTest => _ = peripherals.ext_pin.to_pull_up(),
                 #[cfg(not(feature = "board_v0"))]
-                SensorPage::AdcChannel(_) => peripherals.i2c.pins_to_floating(),
+                SensorPage::AdcChannel(_) | SensorPage::Scope | SensorPage::Battery => {
+                    peripherals.i2c.pins_to_floating()
+                }
                 #[cfg(not(feature = "board_v0"))]
                 SensorPage::ContinuityTest => peripherals.i2c.pins_to_pull_up(),
             }
+            self.scope.clear();
+            self.battery_ticks = 0;
             self.port_init = true;
         }
 
@@ -414,12 +813,30 @@ impl ModeHandler for Sensors {
                         if r == 0 { self.last_reading | mask } else { self.last_reading & !mask }
                     })
                 }
+                SensorPage::Scope => {
+                    update = self.scope.update(&mut peripherals.adc);
+                    None
+                }
+                SensorPage::Battery => {
+                    let ticks = self.battery_ticks;
+                    self.battery_ticks = (ticks + 1) % BATTERY_TICKS;
+                    (ticks == 0).then(|| peripherals.adc.read_vdd())
+                }
             };
 
             if let Some(reading) = reading {
+                if !self.stats_hold {
+                    let stat = self.cur_stat;
+                    let stats_active = self.stats_active;
+                    if let Some(stats) = self.cur_stats() {
+                        let shown = stats.value(stat);
+                        stats.add(reading);
+                        update |= stats_active && stats.value(stat) != shown;
+                    }
+                }
                 if reading != self.last_reading {
                     self.last_reading = reading;
-                    update = true;
+                    update |= !self.stats_active;
                 }
             }
         }
@@ -427,7 +844,19 @@ impl ModeHandler for Sensors {
         if update {
             let mut buf = [0; NUM_CHARS];
             if self.settings_active {
-                self.format_setting(&mut buf, &peripherals.adc.settings);
+                self.format_setting(&mut buf, peripherals.adc.settings());
+            } else if self.stats_active {
+                self.format_stat(&mut buf);
+            } else if matches!(self.cur_page, SensorPage::Scope) {
+                let Some(option) = self.scope.option else {
+                    let mut cols = [0; NUM_COLS];
+                    self.scope.render(&mut cols);
+                    peripherals.display.print_cols(&cols).unwrap();
+                    return;
+                };
+                self.scope.format_option(option, &mut buf);
+            } else if matches!(self.cur_page, SensorPage::Battery) {
+                self.format_battery(&mut buf, &context.battery, peripherals.adc.vdd());
             } else {
                 // update buzzer for continuity test
                 if matches!(self.cur_page, SensorPage::ContinuityTest) {
@@ -1,13 +1,14 @@
 use super::ModeHandler;
 use crate::{
+    board::{Board, DisplaySink},
     utils::{format_buf, format_uint},
-    Context, Display, DisplayPeakCurrent, Event, Peripherals, SavedSettings,
-    Setting as EepromSetting, NUM_CHARS,
+    Context, DisplayPeakCurrent, Event, Peripherals, SavedSettings, Setting as EepromSetting,
+    NUM_CHARS,
 };
 
 const BRIGHTNESS_DEFAULT: u8 = 12;
-const BRIGHTNESS_MAX: u8 = 16;
-const CURRENT_MAX: u8 = 4;
+pub const BRIGHTNESS_MAX: u8 = 16;
+pub const CURRENT_MAX: u8 = 4;
 const CURRENT_DEFAULT: u8 = 1;
 const CURRENT_LEVELS: [u8; CURRENT_MAX as usize] = [4, 6, 9, 13];
 
@@ -52,7 +53,7 @@ impl Settings {
         }
     }
 
-    pub fn apply(&self, display: &mut Display) {
+    pub fn apply(&self, display: &mut impl DisplaySink) {
         display.set_brightness(self.brightness).unwrap();
         display
             .set_peak_current(Self::current_into(self.current))
@@ -62,11 +63,11 @@ impl Settings {
 
 impl ModeHandler for Settings {
     #[inline(never)]
-    fn update(
+    fn update<B: Board>(
         &mut self,
         event: &Option<Event>,
         context: &mut Context,
-        peripherals: &mut Peripherals,
+        peripherals: &mut Peripherals<B>,
     ) {
         let update = event.is_some() || context.need_update();
 
@@ -1,5 +1,8 @@
 use super::ModeHandler;
-use crate::{Context, Event, Peripherals, Rand, COLUMN_GAP, NUM_ROWS, NUM_VIRT_COLS};
+use crate::{
+    board::{Board, DisplaySink},
+    Context, Event, Peripherals, Rand, COLUMN_GAP, NUM_ROWS, NUM_VIRT_COLS,
+};
 use heapless::Vec;
 use random_trait::Random;
 
@@ -193,11 +196,11 @@ impl Traffic {
 
 impl ModeHandler for Traffic {
     #[inline(never)]
-    fn update(
+    fn update<B: Board>(
         &mut self,
         event: &Option<Event>,
         context: &mut Context,
-        peripherals: &mut Peripherals,
+        peripherals: &mut Peripherals<B>,
     ) {
         let mut update = context.need_update();
 
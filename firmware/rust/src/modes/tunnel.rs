@@ -1,5 +1,8 @@
 use super::ModeHandler;
-use crate::{Context, Event, Peripherals, Rand, COLUMN_GAP, NUM_ROWS, NUM_VIRT_COLS};
+use crate::{
+    board::{Board, DisplaySink},
+    Context, Event, Peripherals, Rand, COLUMN_GAP, NUM_ROWS, NUM_VIRT_COLS,
+};
 use heapless::Vec;
 use random_trait::Random;
 
@@ -35,11 +38,11 @@ impl Tunnel {
 
 impl ModeHandler for Tunnel {
     #[inline(never)]
-    fn update(
+    fn update<B: Board>(
         &mut self,
         event: &Option<Event>,
         context: &mut Context,
-        peripherals: &mut Peripherals,
+        peripherals: &mut Peripherals<B>,
     ) {
         let mut update = context.need_update();
 
@@ -2,7 +2,8 @@
 
 use super::ModeHandler;
 use crate::{
-    Context, Display, Event, Peripherals, Rand, COLUMN_GAP, NUM_COLS, NUM_ROWS, NUM_VIRT_COLS,
+    board::{Board, DisplaySink},
+    Context, Event, Peripherals, Rand, COLUMN_GAP, NUM_COLS, NUM_ROWS, NUM_VIRT_COLS,
 };
 use random_trait::Random;
 
@@ -59,7 +60,7 @@ impl Vibes {
         (index + 1) % NUM_VIRT_COLS
     }
 
-    fn render(&mut self, display: &mut Display) {
+    fn render(&mut self, display: &mut impl DisplaySink) {
         let mut cols = [0u8; NUM_COLS];
         let mut col_num = 0;
 
@@ -77,11 +78,11 @@ impl Vibes {
 
 impl ModeHandler for Vibes {
     #[inline(never)]
-    fn update(
+    fn update<B: Board>(
         &mut self,
         event: &Option<Event>,
         context: &mut Context,
-        peripherals: &mut Peripherals,
+        peripherals: &mut Peripherals<B>,
     ) {
         let mut update = context.need_update();
 
@@ -1,4 +1,7 @@
-use embedded_hal::delay::DelayNs;
+use crate::{
+    board::Buzzer,
+    rtttl::{self, parse_rtttl, rtttl_len, rtttl_name, rtttl_song, RtttlSong},
+};
 
 // Note constants
 pub const CN: u8 = 0; // C  (C normal)
@@ -80,65 +83,248 @@ pub fn get_note_frequency(note: u8, octave: u8) -> u32 {
     }
 }
 
-// Play a single note (or rest)
-pub fn play_note(
-    buzzer: &mut crate::tone::Tone,
-    delay: &mut crate::Delay,
-    note_index: u8,
-    octave: u8,
-    timing: u8,
-    timing_unit_ms: u32,
-    octave_adjust: i8,
-) {
-    let octave = (octave as i8 + octave_adjust) as u8;
-    let frequency = get_note_frequency(note_index, octave);
-    if frequency > 0 {
-        buzzer.tone(frequency, timing as u32 * timing_unit_ms);
-    } else {
-        buzzer.no_tone();
-    }
-    delay.delay_ms(timing as u32 * timing_unit_ms);
-    buzzer.no_tone();
-    delay.delay_ms(timing_unit_ms >> 1);
+// Gap between notes, as a fraction of the timing unit
+const NOTE_GAP_SHIFT: u8 = 1;
+
+// Notes of a ringtone received at runtime, END included. Every note takes at least two
+// characters, so this fits any ringtone that fits in the 128 byte I2C message.
+pub const RTTTL_SONG_SIZE: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlayState {
+    Stopped,
+    Playing,
+    Paused,
 }
 
-// Play a song (blocking)
-pub fn play_song(buzzer: &mut crate::tone::Tone, display: &mut crate::Display) {
-    // These could be parameters or settings
-    let timing_unit_ms: u32 = 15; // Default tempo
-    let octave_adjust: i8 = 3;
-    let song = &SUPER_MARIO;
-    let mut delay = crate::Delay::new();
-    let mut note_index = 0;
-    loop {
-        let note = song[note_index];
-        if note == END {
-            break;
+// Plays a song in the background. The main loop calls tick() once per pass with the
+// time since the last call, the buzzer's timer interrupt times each note itself, so
+// the sequencer only has to start the next one when the current note (plus the short
+// gap after it) is over.
+//
+// There is no free-running clock to measure that time, the main loop passes its fixed
+// delay instead. A pass that blocks for longer (I2C scans and sweeps, EEPROM writes,
+// measuring Vdd) stretches the gap after the current note by that much, so the tempo
+// drags a little while a mode is busy. The notes themselves keep their length.
+pub struct Sequencer {
+    // None plays the ringtone in rtttl_notes
+    song: Option<&'static [u16]>,
+    // index in SONGS of the song playing, if it's one of them
+    song_index: Option<u8>,
+    rtttl_notes: [u16; RTTTL_SONG_SIZE],
+    note_index: usize,
+    ms_left: u16,
+    // a hold rewound note_index to replay the cut off note, a second hold mustn't again
+    note_cut: bool,
+    state: PlayState,
+    pub timing_unit_ms: u8,
+    pub octave_adjust: i8,
+}
+
+impl Sequencer {
+    pub const fn new() -> Self {
+        Self {
+            song: None,
+            song_index: None,
+            rtttl_notes: [END; RTTTL_SONG_SIZE],
+            note_index: 0,
+            ms_left: 0,
+            note_cut: false,
+            state: PlayState::Stopped,
+            timing_unit_ms: 15, // default tempo
+            octave_adjust: 3,
+        }
+    }
+
+    fn start(&mut self, song: Option<&'static [u16]>) {
+        self.song = song;
+        self.song_index = None;
+        self.note_index = 0;
+        self.ms_left = 0;
+        self.note_cut = false;
+        self.state = PlayState::Playing;
+    }
+
+    pub fn play(&mut self, song: &'static [u16]) {
+        self.start(Some(song));
+    }
+
+    // Plays SONGS[index] with its own tempo and octave adjust. Playing the same song again
+    // keeps whatever they were changed to since.
+    pub fn play_song(&mut self, index: u8) {
+        let song = &SONGS[index as usize];
+        if self.song_index != Some(index) {
+            self.timing_unit_ms = song.timing_unit_ms;
+            self.octave_adjust = song.octave_adjust;
+        }
+        self.play(song.notes);
+        self.song_index = Some(index);
+    }
+
+    // Converts a ringtone into the sequencer's own buffer and plays it with its tempo. A
+    // bad ringtone leaves nothing in the buffer, so if the previous one was still playing
+    // from it, it ends at the next tick.
+    pub fn play_rtttl(&mut self, text: &[u8]) -> Result<(), rtttl::Error> {
+        match parse_rtttl(text, &mut self.rtttl_notes) {
+            Ok(info) => {
+                self.timing_unit_ms = info.timing_unit_ms;
+                self.octave_adjust = info.octave_adjust;
+                self.start(None);
+                Ok(())
+            }
+            Err(e) => {
+                self.rtttl_notes.fill(END);
+                Err(e)
+            }
+        }
+    }
+
+    pub fn pause(&mut self, buzzer: &mut impl Buzzer) {
+        if self.state == PlayState::Playing {
+            self.state = PlayState::Paused;
+            buzzer.no_tone();
+        }
+    }
+
+    // picks up with the note after the one that was cut off
+    pub fn resume(&mut self) {
+        if self.state == PlayState::Paused {
+            self.ms_left = 0;
+            self.state = PlayState::Playing;
+        }
+    }
+
+    // Makes room for another tone of ms on the buzzer, a note it cuts off is played again
+    // from the start after it.
+    pub fn hold(&mut self, ms: u16) {
+        if self.state != PlayState::Playing {
+            return;
+        }
+        if !self.note_cut && self.ms_left > (self.timing_unit_ms >> NOTE_GAP_SHIFT) as u16 {
+            self.note_index -= 1;
+            self.note_cut = true;
+        }
+        self.ms_left = ms;
+    }
+
+    pub fn stop(&mut self, buzzer: &mut impl Buzzer) {
+        if self.state != PlayState::Stopped {
+            self.state = PlayState::Stopped;
+            buzzer.no_tone();
+        }
+    }
+
+    #[inline(always)]
+    pub fn state(&self) -> PlayState {
+        self.state
+    }
+
+    #[inline(always)]
+    pub fn is_playing(&self) -> bool {
+        self.state == PlayState::Playing
+    }
+
+    // number of notes started so far
+    #[inline(always)]
+    pub fn position(&self) -> usize {
+        self.note_index
+    }
+
+    // note index and octave of the note last started, None for a pause or when stopped
+    pub fn current_note(&self) -> Option<(u8, u8)> {
+        if self.state == PlayState::Stopped || self.note_index == 0 {
+            return None;
         }
-        let octave = ((note & OCTAVE_MASK) >> 12) as u8;
-        let index = ((note & NOTE_MASK) >> 8) as u8;
-        let timing = (note & TIMING_MASK) as u8;
+        let (index, octave, _) = unpack(self.notes()[self.note_index - 1]);
         if index < NOTE_COUNT {
-            display
-                .print_ascii_bytes(NOTES[index as usize].as_bytes())
-                .unwrap();
+            Some((index, octave))
         } else {
-            display.print_ascii_bytes(b" ").unwrap();
+            None
         }
+    }
 
-        play_note(
-            buzzer,
-            &mut delay,
-            index,
-            octave,
-            timing,
-            timing_unit_ms,
-            octave_adjust,
-        );
-        note_index += 1;
+    #[inline(always)]
+    fn notes(&self) -> &[u16] {
+        self.song.unwrap_or(&self.rtttl_notes)
     }
+
+    pub fn tick(&mut self, buzzer: &mut impl Buzzer, elapsed_ms: u16) {
+        if self.state != PlayState::Playing {
+            return;
+        }
+
+        self.ms_left = self.ms_left.saturating_sub(elapsed_ms);
+        if self.ms_left > 0 {
+            return;
+        }
+
+        let note = self.notes().get(self.note_index).copied().unwrap_or(END);
+        if note == END {
+            self.stop(buzzer);
+            return;
+        }
+        self.note_index += 1;
+        self.note_cut = false;
+
+        let (index, octave, timing) = unpack(note);
+        let octave = (octave as i8 + self.octave_adjust) as u8;
+        let duration_ms = timing as u16 * self.timing_unit_ms as u16;
+        let frequency = get_note_frequency(index, octave);
+        if frequency > 0 {
+            buzzer.tone(frequency, duration_ms as u32);
+        } else {
+            buzzer.no_tone();
+        }
+        self.ms_left = duration_ms + (self.timing_unit_ms >> NOTE_GAP_SHIFT) as u16;
+    }
+}
+
+// (note index, octave, timing) of a packed note
+#[inline(always)]
+pub fn unpack(note: u16) -> (u8, u8, u8) {
+    (
+        ((note & NOTE_MASK) >> 8) as u8,
+        ((note & OCTAVE_MASK) >> 12) as u8,
+        (note & TIMING_MASK) as u8,
+    )
+}
+
+pub struct Song {
+    pub title: &'static [u8],
+    pub notes: &'static [u16],
+    pub timing_unit_ms: u8,
+    pub octave_adjust: i8,
 }
 
+impl Song {
+    pub const fn from_rtttl<const N: usize>(
+        text: &'static [u8],
+        song: &'static RtttlSong<N>,
+    ) -> Self {
+        Song {
+            title: rtttl_name(text),
+            notes: &song.notes,
+            timing_unit_ms: song.info.timing_unit_ms,
+            octave_adjust: song.info.octave_adjust,
+        }
+    }
+}
+
+pub const SONGS: [Song; 2] = [
+    Song {
+        title: b"Super Mario Bros",
+        notes: &SUPER_MARIO,
+        timing_unit_ms: 15,
+        octave_adjust: 3,
+    },
+    Song::from_rtttl(TETRIS_RTTTL, &TETRIS),
+];
+
+const TETRIS_RTTTL: &[u8] = b"Tetris (Korobeiniki):d=4,o=5,b=160:\
+    e,8b4,8c,d,8c,8b4,a4,8a4,8c,e,8d,8c,b4.,8c,d,e,c,a4,2a4,8p,\
+    d.,8f,a,8g,8f,e.,8c,e,8d,8c,b4,8b4,8c,d,e,c,a4,a4";
+const TETRIS: RtttlSong<{ rtttl_len(TETRIS_RTTTL) }> = rtttl_song(TETRIS_RTTTL);
+
 pub const SUPER_MARIO: [u16; 322] = [
     n(EN, 5, 4),
     n(EN, 5, 4),
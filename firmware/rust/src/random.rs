@@ -1,5 +1,5 @@
-use avr_device::interrupt::Mutex;
 use core::cell::Cell;
+use critical_section::Mutex;
 use random_trait::Random;
 
 // static struct holding state of the RNG
@@ -17,7 +17,7 @@ pub struct Rand;
 
 impl Rand {
     pub fn seed(seed: u32) {
-        avr_device::interrupt::free(|cs| {
+        critical_section::with(|cs| {
             RNG_STATE.borrow(cs).set(RngState {
                 value: seed,
                 index: 0,
@@ -29,7 +29,7 @@ impl Rand {
 impl Random for Rand {
     type Error = ();
     fn try_fill_bytes(&mut self, buf: &mut [u8]) -> Result<(), Self::Error> {
-        avr_device::interrupt::free(|cs| {
+        critical_section::with(|cs| {
             let mut rng_state = RNG_STATE.borrow(cs).get();
             let mut rand_bytes = rng_state.value.to_le_bytes();
             for e in buf.iter_mut() {
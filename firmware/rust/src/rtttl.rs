@@ -0,0 +1,313 @@
+// RTTTL (Nokia ringtone text) support, converts ringtones like
+//
+//     "Tetris:d=4,o=5,b=160:e6,8b,8c6,d6,8c6,8b,a,8a,8c6,e6,..."
+//
+// into the packed note format from music.rs. Everything here is a const fn, so bundled
+// songs are converted at compile time with rtttl_song while songs received over I2C
+// go through parse_rtttl into the sequencer's own buffer.
+use crate::music::{self, n, pause, END, MAX_OCTAVE};
+
+// RTTTL durations are fractions of a whole note, the shortest (1/32) is two timing units
+// and a whole note 64, so dotted notes still come out as whole units
+const WHOLE_NOTE_TIMING: u16 = 64;
+const MS_PER_MINUTE: u32 = 60_000;
+
+// defaults from the RTTTL spec, used when the defaults section leaves them out
+const DEFAULT_DURATION: u8 = 4;
+const DEFAULT_OCTAVE: u8 = 6;
+const DEFAULT_BPM: u16 = 63;
+
+// bundled songs are shifted up at most this many octaves, same as the hand-written ones
+const MAX_OCTAVE_ADJUST: u8 = 3;
+
+#[derive(ufmt::derive::uDebug, Debug, Clone, Copy, Eq, PartialEq)]
+#[repr(u8)]
+pub enum Error {
+    /// Missing the name, defaults or notes section
+    MissingSection,
+    /// Unknown key or bad value in the defaults section
+    InvalidDefault,
+    /// Not a note letter (a-h or p)
+    InvalidNote,
+    /// Duration other than 1, 2, 4, 8, 16 or 32
+    InvalidDuration,
+    /// Octave outside of what get_note_frequency can play
+    InvalidOctave,
+    /// More notes than fit in the buffer
+    BufferFull,
+}
+
+// What parse_rtttl found besides the notes themselves
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Rtttl {
+    /// Number of notes written, not counting the END marker
+    pub len: usize,
+    /// Timing unit that plays the song at its bpm
+    pub timing_unit_ms: u8,
+    /// Octave adjust that moves the highest note as close to MAX_OCTAVE as allowed
+    pub octave_adjust: i8,
+}
+
+struct Defaults {
+    duration: u8,
+    octave: u8,
+    bpm: u16,
+}
+
+const fn skip_whitespace(text: &[u8], mut i: usize) -> usize {
+    while i < text.len() && text[i].is_ascii_whitespace() {
+        i += 1;
+    }
+    i
+}
+
+// parses a decimal number, None if there are no digits at i
+const fn parse_number(text: &[u8], i: &mut usize) -> Option<u16> {
+    let start = *i;
+    let mut value: u16 = 0;
+    while *i < text.len() && text[*i].is_ascii_digit() {
+        value = value
+            .saturating_mul(10)
+            .saturating_add((text[*i] - b'0') as u16);
+        *i += 1;
+    }
+    if *i == start {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+const fn find_colon(text: &[u8], start: usize) -> Option<usize> {
+    let mut i = start;
+    while i < text.len() {
+        if text[i] == b':' {
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+const fn valid_duration(duration: u16) -> bool {
+    matches!(duration, 1 | 2 | 4 | 8 | 16 | 32)
+}
+
+// parses "d=4,o=5,b=160" between start and end
+const fn parse_defaults(text: &[u8], start: usize, end: usize) -> Result<Defaults, Error> {
+    let mut defaults = Defaults {
+        duration: DEFAULT_DURATION,
+        octave: DEFAULT_OCTAVE,
+        bpm: DEFAULT_BPM,
+    };
+
+    let mut i = skip_whitespace(text, start);
+    while i < end {
+        let key = text[i].to_ascii_lowercase();
+        i = skip_whitespace(text, i + 1);
+        if i >= end || text[i] != b'=' {
+            return Err(Error::InvalidDefault);
+        }
+        i = skip_whitespace(text, i + 1);
+        let value = match parse_number(text, &mut i) {
+            Some(value) => value,
+            None => return Err(Error::InvalidDefault),
+        };
+        match key {
+            b'd' if valid_duration(value) => defaults.duration = value as u8,
+            b'd' => return Err(Error::InvalidDuration),
+            b'o' if value <= MAX_OCTAVE as u16 => defaults.octave = value as u8,
+            b'o' => return Err(Error::InvalidOctave),
+            b'b' if value > 0 => defaults.bpm = value,
+            _ => return Err(Error::InvalidDefault),
+        }
+
+        i = skip_whitespace(text, i);
+        if i < end && text[i] == b',' {
+            i = skip_whitespace(text, i + 1);
+        } else if i < end {
+            return Err(Error::InvalidDefault);
+        }
+    }
+    Ok(defaults)
+}
+
+// Converts an RTTTL string into packed notes followed by END, so notes needs room for
+// one more entry than the ringtone has notes.
+pub const fn parse_rtttl(text: &[u8], notes: &mut [u16]) -> Result<Rtttl, Error> {
+    let name_end = match find_colon(text, 0) {
+        Some(i) => i,
+        None => return Err(Error::MissingSection),
+    };
+    let defaults_end = match find_colon(text, name_end + 1) {
+        Some(i) => i,
+        None => return Err(Error::MissingSection),
+    };
+    let defaults = match parse_defaults(text, name_end + 1, defaults_end) {
+        Ok(defaults) => defaults,
+        Err(e) => return Err(e),
+    };
+
+    let mut len = 0;
+    let mut max_octave = 0;
+    let mut i = skip_whitespace(text, defaults_end + 1);
+    while i < text.len() {
+        // [duration] note [#] [.] [octave] [.]
+        let duration = match parse_number(text, &mut i) {
+            Some(duration) if valid_duration(duration) => duration,
+            Some(_) => return Err(Error::InvalidDuration),
+            None => defaults.duration as u16,
+        };
+
+        if i >= text.len() {
+            return Err(Error::InvalidNote);
+        }
+        let mut note = match text[i].to_ascii_lowercase() {
+            b'c' => music::CN,
+            b'd' => music::DN,
+            b'e' => music::EN,
+            b'f' => music::FN,
+            b'g' => music::GN,
+            b'a' => music::AN,
+            b'b' | b'h' => music::BN,
+            b'p' => music::SILENCE,
+            _ => return Err(Error::InvalidNote),
+        };
+        i += 1;
+
+        let mut sharp = false;
+        if i < text.len() && text[i] == b'#' {
+            sharp = true;
+            i += 1;
+        }
+
+        let mut dotted = false;
+        if i < text.len() && text[i] == b'.' {
+            dotted = true;
+            i += 1;
+        }
+
+        let mut octave = match parse_number(text, &mut i) {
+            Some(octave) if octave <= MAX_OCTAVE as u16 => octave as u8,
+            Some(_) => return Err(Error::InvalidOctave),
+            None => defaults.octave,
+        };
+
+        // some ringtones put the dot after the octave
+        if i < text.len() && text[i] == b'.' {
+            dotted = true;
+            i += 1;
+        }
+
+        if sharp && note != music::SILENCE {
+            note += 1;
+            // e# and b# are just the next note up
+            if note == music::NOTE_COUNT {
+                note = music::CN;
+                octave += 1;
+                if octave > MAX_OCTAVE {
+                    return Err(Error::InvalidOctave);
+                }
+            }
+        }
+
+        let mut timing = WHOLE_NOTE_TIMING / duration;
+        if dotted {
+            timing += timing / 2;
+        }
+
+        // keep the last entry for END
+        if len + 1 >= notes.len() {
+            return Err(Error::BufferFull);
+        }
+        notes[len] = if note == music::SILENCE {
+            pause(timing as u8)
+        } else {
+            if octave > max_octave {
+                max_octave = octave;
+            }
+            n(note, octave, timing as u8)
+        };
+        len += 1;
+
+        i = skip_whitespace(text, i);
+        if i < text.len() {
+            if text[i] != b',' {
+                return Err(Error::InvalidNote);
+            }
+            i = skip_whitespace(text, i + 1);
+        }
+    }
+    if len >= notes.len() {
+        return Err(Error::BufferFull);
+    }
+    notes[len] = END;
+
+    let mut timing_unit_ms = MS_PER_MINUTE * 4 / WHOLE_NOTE_TIMING as u32 / defaults.bpm as u32;
+    if timing_unit_ms == 0 {
+        timing_unit_ms = 1;
+    } else if timing_unit_ms > u8::MAX as u32 {
+        timing_unit_ms = u8::MAX as u32;
+    }
+
+    let mut octave_adjust = MAX_OCTAVE - max_octave;
+    if octave_adjust > MAX_OCTAVE_ADJUST {
+        octave_adjust = MAX_OCTAVE_ADJUST;
+    }
+
+    Ok(Rtttl {
+        len,
+        timing_unit_ms: timing_unit_ms as u8,
+        octave_adjust: octave_adjust as i8,
+    })
+}
+
+// Number of entries rtttl_notes needs for a ringtone, END included
+pub const fn rtttl_len(text: &[u8]) -> usize {
+    let mut i = match find_colon(text, 0) {
+        Some(i) => match find_colon(text, i + 1) {
+            Some(i) => i + 1,
+            None => panic!("invalid RTTTL: missing section"),
+        },
+        None => panic!("invalid RTTTL: missing section"),
+    };
+
+    let mut len = 2; // last note has no comma, plus END
+    while i < text.len() {
+        if text[i] == b',' {
+            len += 1;
+        }
+        i += 1;
+    }
+    len
+}
+
+// A ringtone converted at compile time
+pub struct RtttlSong<const N: usize> {
+    pub notes: [u16; N],
+    pub info: Rtttl,
+}
+
+// Compile time conversion for bundled songs, an invalid ringtone fails the build:
+//
+//     const SONG: RtttlSong<{ rtttl_len(SONG_RTTTL) }> = rtttl_song(SONG_RTTTL);
+pub const fn rtttl_song<const N: usize>(text: &[u8]) -> RtttlSong<N> {
+    let mut notes = [END; N];
+    match parse_rtttl(text, &mut notes) {
+        Ok(info) => RtttlSong { notes, info },
+        Err(Error::MissingSection) => panic!("invalid RTTTL: missing section"),
+        Err(Error::InvalidDefault) => panic!("invalid RTTTL: bad default"),
+        Err(Error::InvalidNote) => panic!("invalid RTTTL: bad note"),
+        Err(Error::InvalidDuration) => panic!("invalid RTTTL: bad duration"),
+        Err(Error::InvalidOctave) => panic!("invalid RTTTL: bad octave"),
+        Err(Error::BufferFull) => panic!("invalid RTTTL: too many notes"),
+    }
+}
+
+// Name section of a ringtone
+pub const fn rtttl_name(text: &[u8]) -> &[u8] {
+    match find_colon(text, 0) {
+        Some(i) => text.split_at(i).0,
+        None => text,
+    }
+}
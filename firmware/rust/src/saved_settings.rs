@@ -1,21 +1,248 @@
+// Current layout version, stored at Setting::Version. Bump it whenever the layout below
+// changes and add a step to SavedSettings::migrate that converts the previous layout.
+pub const SETTINGS_VERSION: u8 = 1;
+
+// Badges flashed before the layout was versioned never wrote the version byte, so an
+// erased byte means either the original (v0) layout or a fresh EEPROM.
+const LEGACY_VERSION: u8 = 0xFF;
+const ERASED: u8 = 0xFF;
+
+// v1 layout, a 2 byte header followed by the checksummed settings. Settings that change
+// all the time (mode/page changes) live in wear-leveling rings instead, see below.
 #[allow(dead_code)]
+#[repr(u16)]
 pub enum Setting {
     Version = 0x00,
-    Brightness = 0x01,
-    Current = 0x02,
-    Tone = 0x03,
-    Name = 0x04, // 8 bytes wide
-    LastMode = 0x0C,
-    RandomPage = 0x0D,
-    SensorPage = 0x0E,
-    I2CPage = 0x0F,
+    Checksum = 0x01,
+    Brightness = 0x02,
+    Current = 0x03,
+    Tone = 0x04,
+    Name = 0x05, // 8 bytes wide
+    SensorPage = 0x0D,
+    I2CPage = 0x0E,
+    I2CSpeed = 0x0F,
+    I2CRiseTime = 0x10, // in 10ns steps
+    I2CAddress = 0x11,
+    AdcSettings = 0x12, // ADC_SETTINGS_SIZE bytes wide
+    BatteryCell = 0x1C,
+    // wear-leveled
+    LastMode = WEAR_RINGS_START,
+    RandomPage = WEAR_RINGS_START + WEAR_RING_SIZE,
 }
 
+const SETTINGS_START: u16 = Setting::Brightness as u16;
+const SETTINGS_END: u16 = Setting::BatteryCell as u16 + 1;
+const SETTINGS_LEN: usize = (SETTINGS_END - SETTINGS_START) as usize;
+
+// values used for a fresh or corrupt EEPROM, in layout order
+const DEFAULTS: [u8; SETTINGS_LEN] = [
+    12, // Brightness
+    1,  // Current, 6.4mA
+    1,  // Tone
+    b' ', b'N', b'O', b'N', b'I', b'K', b'0', b' ', // Name
+    0,    // SensorPage
+    0,    // I2CPage
+    0,    // I2CSpeed, Standard
+    35,   // I2CRiseTime, 350ns
+    0x13, // I2CAddress
+    0, 6, 1, 7, 0, 3, 5, 0, 10, 10, // AdcSettings, AdcSettings::default()
+    0,  // BatteryCell, LIR2032
+];
+
+// Each wear-leveled setting gets a ring of WEAR_RING_SLOTS status bytes followed by as
+// many value bytes (AVR101 style). A write goes to the slot after the current one and
+// sets its status to the current status + 1, so the current slot is the last one before
+// that sequence breaks. The value is written before the status, so an interrupted write
+// leaves the old value in place.
+const WEAR_RINGS_START: u16 = 0x40;
+const WEAR_RING_SLOTS: u16 = 16;
+const WEAR_RING_SIZE: u16 = 2 * WEAR_RING_SLOTS;
+
+// defaults for the wear-leveled settings, in ring order
+const WEAR_RING_DEFAULTS: [u8; 2] = [
+    1, // LastMode, Nametag
+    0, // RandomPage
+];
+
+// Names received in Link mode fill the rest of the EEPROM, NUM_CONTACT_SLOTS slots of
+// CONTACT_SIZE bytes used as a ring. One slot is always kept free (erased first byte) to
+// mark where the next name goes, so the newest contact is the one right before it and
+// the oldest is overwritten once the ring is full. Contacts are not checksummed and
+// restoring the default settings keeps them.
+const CONTACTS_START: u16 = WEAR_RINGS_START + WEAR_RING_DEFAULTS.len() as u16 * WEAR_RING_SIZE;
+const CONTACT_SIZE: u16 = 8;
+const NUM_CONTACT_SLOTS: u16 = 16;
+pub const MAX_CONTACTS: usize = NUM_CONTACT_SLOTS as usize - 1;
+
+// v0 layout had no header, the settings from Brightness to Name started at 0x01 and the
+// pages followed them
+const V0_SETTINGS_START: u16 = 0x01;
+const V0_LAST_MODE: u16 = 0x0C;
+const V0_RANDOM_PAGE: u16 = 0x0D;
+const V0_SENSOR_PAGE: u16 = 0x0E;
+const V0_I2C_PAGE: u16 = 0x0F;
+
 pub struct SavedSettings(crate::Eeprom);
 
 impl SavedSettings {
     pub fn new(eeprom: crate::Eeprom) -> Self {
-        SavedSettings(eeprom)
+        let mut settings = SavedSettings(eeprom);
+        settings.migrate();
+        settings
+    }
+
+    // Brings the EEPROM up to the current layout, falling back to defaults when the
+    // contents can't be trusted (bad checksum, unknown version).
+    fn migrate(&mut self) {
+        match self.0.read_byte(Setting::Version as u16) {
+            SETTINGS_VERSION => {
+                if !self.checksum_valid() {
+                    self.restore_defaults();
+                }
+            }
+            LEGACY_VERSION => self.migrate_from_v0(),
+            _ => self.restore_defaults(),
+        }
+    }
+
+    // a fresh EEPROM is all erased, so it ends up with the defaults here too
+    fn migrate_from_v0(&mut self) {
+        let mut buf = DEFAULTS;
+        let name_end = (V0_LAST_MODE - V0_SETTINGS_START) as usize;
+        self.0
+            .read(V0_SETTINGS_START, &mut buf[..name_end])
+            .unwrap();
+        buf[(Setting::SensorPage as u16 - SETTINGS_START) as usize] =
+            self.0.read_byte(V0_SENSOR_PAGE);
+        buf[(Setting::I2CPage as u16 - SETTINGS_START) as usize] = self.0.read_byte(V0_I2C_PAGE);
+
+        // settings that were never saved on the old layout get their default
+        for (byte, default) in buf.iter_mut().zip(DEFAULTS) {
+            if *byte == ERASED {
+                *byte = default;
+            }
+        }
+
+        // the rings are past the v0 settings, fill them before the settings are overwritten
+        let last_mode = self.0.read_byte(V0_LAST_MODE);
+        let random_page = self.0.read_byte(V0_RANDOM_PAGE);
+        self.write_wear_ring(Setting::LastMode as u16, last_mode);
+        self.write_wear_ring(Setting::RandomPage as u16, random_page);
+        self.write_settings(&buf);
+    }
+
+    pub fn restore_defaults(&mut self) {
+        self.write_settings(&DEFAULTS);
+        for (ring, default) in WEAR_RING_DEFAULTS.into_iter().enumerate() {
+            self.write_wear_ring(WEAR_RINGS_START + ring as u16 * WEAR_RING_SIZE, default);
+        }
+    }
+
+    fn write_settings(&mut self, buf: &[u8; SETTINGS_LEN]) {
+        self.0.write(SETTINGS_START, buf).unwrap();
+        self.0.write_byte(Setting::Version as u16, SETTINGS_VERSION);
+        self.update_checksum();
+    }
+
+    fn checksum(&self) -> u8 {
+        // rotate before each xor so swapped bytes change the checksum too
+        (SETTINGS_START..SETTINGS_END).fold(SETTINGS_VERSION, |sum, address| {
+            sum.rotate_left(1) ^ self.0.read_byte(address)
+        })
+    }
+
+    fn checksum_valid(&self) -> bool {
+        self.0.read_byte(Setting::Checksum as u16) == self.checksum()
+    }
+
+    fn update_checksum(&mut self) {
+        let checksum = self.checksum();
+        if self.0.read_byte(Setting::Checksum as u16) != checksum {
+            self.0.write_byte(Setting::Checksum as u16, checksum);
+        }
+    }
+
+    #[inline(always)]
+    fn is_wear_leveled(address: u16) -> bool {
+        address >= WEAR_RINGS_START
+    }
+
+    // index of the slot holding the ring's current value
+    fn wear_ring_slot(&self, ring: u16) -> u16 {
+        let mut status = self.0.read_byte(ring);
+        for slot in 0..WEAR_RING_SLOTS - 1 {
+            let next_status = self.0.read_byte(ring + slot + 1);
+            if next_status != status.wrapping_add(1) {
+                return slot;
+            }
+            status = next_status;
+        }
+        WEAR_RING_SLOTS - 1
+    }
+
+    fn read_wear_ring(&self, ring: u16) -> u8 {
+        let slot = self.wear_ring_slot(ring);
+        let value = self.0.read_byte(ring + WEAR_RING_SLOTS + slot);
+        if value == ERASED {
+            WEAR_RING_DEFAULTS[((ring - WEAR_RINGS_START) / WEAR_RING_SIZE) as usize]
+        } else {
+            value
+        }
+    }
+
+    // read-before-write, rewriting the current value doesn't use up a slot
+    fn write_wear_ring(&mut self, ring: u16, value: u8) {
+        let slot = self.wear_ring_slot(ring);
+        if self.0.read_byte(ring + WEAR_RING_SLOTS + slot) == value {
+            return;
+        }
+
+        let status = self.0.read_byte(ring + slot);
+        let next_slot = (slot + 1) % WEAR_RING_SLOTS;
+        self.0.write_byte(ring + WEAR_RING_SLOTS + next_slot, value);
+        self.0.write_byte(ring + next_slot, status.wrapping_add(1));
+    }
+
+    fn contact_address(slot: u16) -> u16 {
+        CONTACTS_START + (slot % NUM_CONTACT_SLOTS) * CONTACT_SIZE
+    }
+
+    // slot the next contact goes in, the first one if the ring lost its free slot
+    fn contacts_free_slot(&self) -> u16 {
+        (0..NUM_CONTACT_SLOTS)
+            .find(|&slot| self.0.read_byte(Self::contact_address(slot)) == ERASED)
+            .unwrap_or(0)
+    }
+
+    // contact index back from the newest, false if there is no contact at index
+    pub fn read_contact(&self, index: usize, buf: &mut [u8]) -> bool {
+        if index >= MAX_CONTACTS {
+            return false;
+        }
+        let slot = self.contacts_free_slot() + NUM_CONTACT_SLOTS - 1 - index as u16;
+        let address = Self::contact_address(slot);
+        if self.0.read_byte(address) == ERASED {
+            return false;
+        }
+        self.0.read(address, buf).unwrap();
+        true
+    }
+
+    // adds a contact unless it's already in the list
+    pub fn save_contact(&mut self, name: &[u8]) {
+        let mut buf = [0; CONTACT_SIZE as usize];
+        let mut index = 0;
+        while self.read_contact(index, &mut buf) {
+            if buf[..] == *name {
+                return;
+            }
+            index += 1;
+        }
+
+        // an interrupted write leaves no free slot, the ring then restarts at slot 0
+        let slot = self.contacts_free_slot();
+        self.0.write(Self::contact_address(slot), name).unwrap();
+        self.0.write_byte(Self::contact_address(slot + 1), ERASED);
     }
 
     #[inline(always)]
@@ -28,14 +255,18 @@ impl SavedSettings {
         self.read_setting_byte(setting) != 0
     }
 
-    #[inline(always)]
     pub fn read_setting_byte(&self, setting: Setting) -> u8 {
-        self.0.read_byte(setting as u16)
+        let address = setting as u16;
+        if Self::is_wear_leveled(address) {
+            self.read_wear_ring(address)
+        } else {
+            self.0.read_byte(address)
+        }
     }
 
-    #[inline(always)]
     pub fn save_setting(&mut self, setting: Setting, buf: &[u8]) {
         self.0.write(setting as u16, buf).unwrap();
+        self.update_checksum();
     }
 
     #[inline(always)]
@@ -43,8 +274,13 @@ impl SavedSettings {
         self.save_setting_byte(setting, value as u8);
     }
 
-    #[inline(always)]
     pub fn save_setting_byte(&mut self, setting: Setting, value: u8) {
-        self.0.write_byte(setting as u16, value);
+        let address = setting as u16;
+        if Self::is_wear_leveled(address) {
+            self.write_wear_ring(address, value);
+        } else if self.0.read_byte(address) != value {
+            self.0.write_byte(address, value);
+            self.update_checksum();
+        }
     }
-}
\ No newline at end of file
+}
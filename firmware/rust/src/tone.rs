@@ -1,4 +1,4 @@
-use crate::CoreClock;
+use crate::{board::Buzzer, CoreClock};
 use avrxmega_hal::{
     clock::Clock,
     port::{mode::Output, PA5},
@@ -98,8 +98,10 @@ impl Tone {
 
         Self {}
     }
+}
 
-    pub fn tone(&mut self, frequency: u32, duration: u32) {
+impl Buzzer for Tone {
+    fn tone(&mut self, frequency: u32, duration: u32) {
         if frequency == 0 {
             self.no_tone();
             return;
@@ -147,7 +149,7 @@ impl Tone {
         });
     }
 
-    pub fn no_tone(&mut self) {
+    fn no_tone(&mut self) {
         avr_device::interrupt::free(|cs| {
             let state_opt_refcell = TONE_STATE.borrow(cs);
             let mut state_opt = state_opt_refcell.borrow_mut();
@@ -46,6 +46,11 @@ pub trait EnumCycle: Sized + Copy {
             self
         }
     }
+
+    fn prev_wrapping(self) -> Self {
+        let prev = (self.to_u8() + Self::COUNT - 1) % Self::COUNT;
+        Self::from_u8(prev)
+    }
 }
 
 // Util function to format an unsigned integer with a prefix and suffix value
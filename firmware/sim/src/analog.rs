@@ -0,0 +1,107 @@
+use crate::{
+    adc::{AdcChannel, AdcSettings, Resolution, VDD_DEFAULT},
+    board::AnalogSource,
+    utils::EnumCycle,
+};
+
+// Simulated ADC0. Each channel returns a fixed voltage (in 10mV units, like the readings)
+// that can be changed at runtime, converted to a raw reading with the current reference and resolution. The
+// supply voltage can be changed too, Vdd-referenced readings follow it once it's measured.
+pub struct SimAdc {
+    settings: AdcSettings,
+    enabled: bool,
+    voltages: [u16; AdcChannel::COUNT as usize],
+    temp_c: u16,
+    supply: u16,
+    vdd: u16,
+}
+
+impl SimAdc {
+    pub fn new() -> Self {
+        let mut adc = Self {
+            settings: AdcSettings::default(),
+            enabled: false,
+            voltages: [0; AdcChannel::COUNT as usize],
+            temp_c: 25,
+            supply: VDD_DEFAULT,
+            vdd: VDD_DEFAULT,
+        };
+        adc.set_voltage(AdcChannel::Vref, 110);
+        adc
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_voltage(&mut self, channel: AdcChannel, voltage: u16) {
+        self.voltages[channel as usize] = voltage;
+    }
+
+    pub fn set_temp_c(&mut self, temp_c: u16) {
+        self.temp_c = temp_c;
+    }
+
+    pub fn set_supply(&mut self, supply: u16) {
+        self.supply = supply;
+    }
+
+    // inverse of AdcSettings::voltage_from_raw, clamped to the ADC's range. Vdd-referenced
+    // readings are taken against the actual supply, not the last measured Vdd.
+    fn raw_from_voltage(&self, voltage: u16) -> u16 {
+        let raw_max: u32 = match self.settings.resolution {
+            Resolution::_10BIT => 1023,
+            Resolution::_8BIT => 255,
+        };
+        let full_scale = self
+            .settings
+            .voltage_from_raw(raw_max as u16, self.supply)
+            .max(1) as u32;
+        ((voltage as u32 * raw_max) / full_scale).min(raw_max) as u16
+    }
+}
+
+impl Default for SimAdc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AnalogSource for SimAdc {
+    fn settings(&self) -> &AdcSettings {
+        &self.settings
+    }
+
+    fn settings_mut(&mut self) -> &mut AdcSettings {
+        &mut self.settings
+    }
+
+    fn apply_settings(&mut self) {
+        self.enabled = true;
+    }
+
+    fn disable(&mut self) {
+        self.enabled = false;
+    }
+
+    fn read_raw_nonblocking(&mut self, channel: AdcChannel) -> Option<u16> {
+        Some(self.raw_from_voltage(self.voltages[channel as usize]))
+    }
+
+    fn read_temp_nonblocking(&mut self, use_f: bool) -> Option<u16> {
+        if use_f {
+            Some(self.temp_c * 9 / 5 + 32)
+        } else {
+            Some(self.temp_c)
+        }
+    }
+
+    fn vdd(&self) -> u16 {
+        self.vdd
+    }
+
+    fn read_vdd(&mut self) -> u16 {
+        self.vdd = self.supply;
+        self.vdd
+    }
+}
@@ -0,0 +1,58 @@
+use core::convert::Infallible;
+use embedded_hal::digital::{ErrorType, InputPin};
+use std::{cell::Cell, rc::Rc};
+
+// long enough to register a press but released well before HOLD_CYCLES in input.rs
+pub const PRESS_TICKS: u8 = 5;
+// held past HOLD_CYCLES in input.rs
+pub const HOLD_TICKS: u8 = 25;
+// held past the both-held threshold in input.rs
+pub const BOTH_HOLD_TICKS: u8 = 15;
+
+// Simulated push button. The pin reads low (pressed, buttons are pulled up) for as
+// many main loop ticks as requested, so the firmware's debouncer sees the same
+// sequence of levels it would see from a finger on the real badge.
+#[derive(Clone, Default)]
+pub struct ButtonState(Rc<Cell<u8>>);
+
+impl ButtonState {
+    pub fn pin(&self) -> SimButton {
+        SimButton(self.0.clone())
+    }
+
+    pub fn press(&self) {
+        self.hold_for(PRESS_TICKS);
+    }
+
+    pub fn hold(&self) {
+        self.hold_for(HOLD_TICKS);
+    }
+
+    pub fn hold_for(&self, ticks: u8) {
+        self.0.set(self.0.get().max(ticks));
+    }
+
+    pub fn is_pressed(&self) -> bool {
+        self.0.get() > 0
+    }
+
+    pub(crate) fn tick(&self) {
+        self.0.set(self.0.get().saturating_sub(1));
+    }
+}
+
+pub struct SimButton(Rc<Cell<u8>>);
+
+impl ErrorType for SimButton {
+    type Error = Infallible;
+}
+
+impl InputPin for SimButton {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.0.get() == 0)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.0.get() > 0)
+    }
+}
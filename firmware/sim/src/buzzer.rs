@@ -0,0 +1,40 @@
+use crate::board::Buzzer;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ToneEvent {
+    Tone { frequency: u32, duration: u32 },
+    NoTone,
+}
+
+// Simulated piezo buzzer, records every request instead of toggling PA5.
+#[derive(Default)]
+pub struct SimBuzzer {
+    events: Vec<ToneEvent>,
+}
+
+impl SimBuzzer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn take_events(&mut self) -> Vec<ToneEvent> {
+        core::mem::take(&mut self.events)
+    }
+}
+
+impl Buzzer for SimBuzzer {
+    fn tone(&mut self, frequency: u32, duration: u32) {
+        if frequency == 0 {
+            self.no_tone();
+            return;
+        }
+        self.events.push(ToneEvent::Tone {
+            frequency,
+            duration,
+        });
+    }
+
+    fn no_tone(&mut self) {
+        self.events.push(ToneEvent::NoTone);
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{board::DisplaySink, font, modes::*, DisplayPeakCurrent};
+use core::convert::Infallible;
+use hcms_29xx::CHAR_WIDTH;
+
+const DOT_ON: &str = "██";
+const DOT_OFF: &str = "  ";
+
+// Simulated HCMS-2914. Keeps the 40 column dot register the real display latches and
+// counts frames so callers can tell when a mode actually pushed new data. Frames can
+// also be recorded, which the golden-frame tests use.
+pub struct SimDisplay {
+    cols: [u8; NUM_COLS],
+    brightness: u8,
+    peak_current_ma10: u8, // tenths of mA
+    frames: u32,
+    recording: Option<Vec<[u8; NUM_COLS]>>,
+}
+
+impl SimDisplay {
+    pub fn new() -> Self {
+        Self {
+            cols: [0; NUM_COLS],
+            brightness: 12,
+            peak_current_ma10: 64,
+            frames: 0,
+            recording: None,
+        }
+    }
+
+    pub fn cols(&self) -> &[u8; NUM_COLS] {
+        &self.cols
+    }
+
+    pub fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    pub fn peak_current_ma10(&self) -> u8 {
+        self.peak_current_ma10
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Vec::new());
+    }
+
+    // returns the frames pushed since the last call, keeps recording
+    pub fn take_recording(&mut self) -> Vec<[u8; NUM_COLS]> {
+        self.recording
+            .as_mut()
+            .map(core::mem::take)
+            .unwrap_or_default()
+    }
+
+    fn push_frame(&mut self) {
+        self.frames = self.frames.wrapping_add(1);
+        if let Some(recording) = self.recording.as_mut() {
+            recording.push(self.cols);
+        }
+    }
+
+    // Renders the dot matrix row by row with COLUMN_GAP blank columns between characters,
+    // the same spacing the modes assume for their virtual columns. Row 0 is the LSB.
+    pub fn render_rows(&self) -> [String; NUM_ROWS] {
+        core::array::from_fn(|row| {
+            let mut line = String::new();
+            for (char_index, char_cols) in self.cols.chunks(CHAR_WIDTH).enumerate() {
+                if char_index > 0 {
+                    for _ in 0..COLUMN_GAP {
+                        line.push_str(DOT_OFF);
+                    }
+                }
+                for col in char_cols {
+                    line.push_str(if col & (1 << row) != 0 {
+                        DOT_ON
+                    } else {
+                        DOT_OFF
+                    });
+                }
+            }
+            line
+        })
+    }
+}
+
+impl DisplaySink for SimDisplay {
+    type Error = Infallible;
+
+    fn print_ascii_bytes(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        for (char_cols, &c) in self.cols.chunks_mut(CHAR_WIDTH).zip(bytes) {
+            char_cols.copy_from_slice(font::glyph(c));
+        }
+        self.push_frame();
+        Ok(())
+    }
+
+    fn print_cols(&mut self, cols: &[u8]) -> Result<(), Self::Error> {
+        let len = cols.len().min(NUM_COLS);
+        self.cols[..len].copy_from_slice(&cols[..len]);
+        self.push_frame();
+        Ok(())
+    }
+
+    fn set_brightness(&mut self, brightness: u8) -> Result<(), Self::Error> {
+        self.brightness = brightness;
+        Ok(())
+    }
+
+    fn set_peak_current(&mut self, peak_current: DisplayPeakCurrent) -> Result<(), Self::Error> {
+        self.peak_current_ma10 = match peak_current {
+            DisplayPeakCurrent::Max4_0Ma => 40,
+            DisplayPeakCurrent::Max6_4Ma => 64,
+            DisplayPeakCurrent::Max9_3Ma => 93,
+            DisplayPeakCurrent::Max12_8Ma => 128,
+        };
+        Ok(())
+    }
+}
+
+impl Default for SimDisplay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
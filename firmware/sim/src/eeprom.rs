@@ -0,0 +1,92 @@
+use std::{fs, io, path::PathBuf};
+
+// ATtiny1604 EEPROM size
+pub const EEPROM_SIZE: usize = 256;
+
+#[derive(Debug)]
+pub struct OutOfBoundsError;
+
+// Simulated EEPROM with the same interface as avrxmega_hal::eeprom::Eeprom. Starts out
+// erased (0xFF) like a freshly flashed badge, optionally backed by a file so settings
+// survive between simulator runs.
+pub struct Eeprom {
+    data: [u8; EEPROM_SIZE],
+    path: Option<PathBuf>,
+}
+
+impl Eeprom {
+    pub fn new() -> Self {
+        Self {
+            data: [0xFF; EEPROM_SIZE],
+            path: None,
+        }
+    }
+
+    pub fn with_data(data: [u8; EEPROM_SIZE]) -> Self {
+        Self { data, path: None }
+    }
+
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let mut data = [0xFF; EEPROM_SIZE];
+        match fs::read(&path) {
+            Ok(bytes) => {
+                let len = bytes.len().min(EEPROM_SIZE);
+                data[..len].copy_from_slice(&bytes[..len]);
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        Ok(Self {
+            data,
+            path: Some(path),
+        })
+    }
+
+    pub fn data(&self) -> &[u8; EEPROM_SIZE] {
+        &self.data
+    }
+
+    pub fn read(&self, offset: u16, buf: &mut [u8]) -> Result<(), OutOfBoundsError> {
+        let start = offset as usize;
+        let end = start + buf.len();
+        if end > EEPROM_SIZE {
+            return Err(OutOfBoundsError);
+        }
+        buf.copy_from_slice(&self.data[start..end]);
+        Ok(())
+    }
+
+    pub fn read_byte(&self, offset: u16) -> u8 {
+        self.data[offset as usize]
+    }
+
+    pub fn write(&mut self, offset: u16, buf: &[u8]) -> Result<(), OutOfBoundsError> {
+        let start = offset as usize;
+        let end = start + buf.len();
+        if end > EEPROM_SIZE {
+            return Err(OutOfBoundsError);
+        }
+        self.data[start..end].copy_from_slice(buf);
+        self.flush();
+        Ok(())
+    }
+
+    pub fn write_byte(&mut self, offset: u16, data: u8) {
+        self.data[offset as usize] = data;
+        self.flush();
+    }
+
+    fn flush(&self) {
+        if let Some(path) = &self.path {
+            // best effort, a failed save shouldn't take down the simulated badge
+            fs::write(path, self.data).ok();
+        }
+    }
+}
+
+impl Default for Eeprom {
+    fn default() -> Self {
+        Self::new()
+    }
+}
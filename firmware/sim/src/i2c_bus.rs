@@ -0,0 +1,298 @@
+use crate::{
+    board::I2cBus,
+    i2c::{
+        ClientReceiveHandler, ClientRequestHandler, Direction, Error, SniffEvent, I2C_BUFFER_SIZE,
+    },
+};
+use std::collections::VecDeque;
+
+// A device on the bus with 256 byte registers. Like most sensors, a write sets the register
+// pointer with its first byte and stores the rest from there, reads continue from the
+// pointer. Above max_speed the device stops ACKing, like a slow part or a bus with too
+// much capacitance. A hung device stretches the clock forever once addressed.
+struct SimDevice {
+    address: u8,
+    registers: [u8; 256],
+    pointer: u8,
+    max_speed: u32,
+    hung: bool,
+}
+
+// Simulated TWI0 on the JST port, with devices that ACK their address and act as simple
+// register files. A remote host talking to our client address is simulated with
+// client_push (host write) and client_request (host read). A device stuck mid-transfer
+// holding SDA low is simulated with hold_sda, the bus stays busy until it is recovered.
+// Traffic between other devices for the sniffer is simulated with bus_traffic.
+#[derive(Default)]
+pub struct SimI2c {
+    devices: Vec<SimDevice>,
+    host_speed: Option<u32>,
+    host_rise_time_ns: u16,
+    client_address: Option<u8>,
+    client_rx: VecDeque<u8>,
+    client_response: Vec<u8>,
+    on_receive: Option<ClientReceiveHandler>,
+    on_request: Option<ClientRequestHandler>,
+    pulled_up: bool,
+    sda_held: bool,
+    sda_shorted: bool,
+    sniffing: bool,
+    sniff_ack: bool,
+    sniff_events: VecDeque<SniffEvent>,
+}
+
+impl SimI2c {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_device(&mut self, address: u8) {
+        if self.device(address).is_none() {
+            self.devices.push(SimDevice {
+                address,
+                registers: [0; 256],
+                pointer: 0,
+                max_speed: u32::MAX,
+                hung: false,
+            });
+        }
+    }
+
+    pub fn remove_device(&mut self, address: u8) {
+        self.devices.retain(|device| device.address != address);
+    }
+
+    pub fn device_registers(&mut self, address: u8) -> Option<&mut [u8; 256]> {
+        self.device(address).map(|device| &mut device.registers)
+    }
+
+    pub fn set_device_max_speed(&mut self, address: u8, max_speed: u32) {
+        if let Some(device) = self.device(address) {
+            device.max_speed = max_speed;
+        }
+    }
+
+    pub fn set_device_hung(&mut self, address: u8, hung: bool) {
+        if let Some(device) = self.device(address) {
+            device.hung = hung;
+        }
+    }
+
+    fn device(&mut self, address: u8) -> Option<&mut SimDevice> {
+        self.devices
+            .iter_mut()
+            .find(|device| device.address == address)
+    }
+
+    pub fn host_speed(&self) -> Option<u32> {
+        self.host_speed
+    }
+
+    // a transfer between another host and device, logged while sniffing (read data is
+    // the device's, so only the address shows up, write data only when ACKing writes)
+    pub fn bus_traffic(&mut self, address: u8, direction: Direction, bytes: &[u8]) {
+        if !self.sniffing {
+            return;
+        }
+        self.sniff_events
+            .push_back(SniffEvent::Start(address, direction));
+        if direction == Direction::Write && self.sniff_ack {
+            self.sniff_events
+                .extend(bytes.iter().map(|&byte| SniffEvent::Data(byte)));
+        }
+    }
+
+    pub fn is_sniffing(&self) -> bool {
+        self.sniffing
+    }
+
+    pub fn is_sniff_acking(&self) -> bool {
+        self.sniffing && self.sniff_ack
+    }
+
+    // shorted lines stay low through bus_recover
+    pub fn hold_sda(&mut self, shorted: bool) {
+        self.sda_held = true;
+        self.sda_shorted = shorted;
+    }
+
+    pub fn is_sda_held(&self) -> bool {
+        self.sda_held
+    }
+
+    pub fn host_rise_time_ns(&self) -> u16 {
+        self.host_rise_time_ns
+    }
+
+    pub fn client_address(&self) -> Option<u8> {
+        self.client_address
+    }
+
+    pub fn is_pulled_up(&self) -> bool {
+        self.pulled_up
+    }
+
+    // queues bytes as if a remote host wrote them to our client address (or hands them to
+    // the receive handler), false if the write is NAKed (client disabled, previous write
+    // not read yet or buffer full)
+    pub fn client_push(&mut self, bytes: &[u8]) -> bool {
+        if self.client_address.is_none() || !self.client_rx.is_empty() {
+            return false;
+        }
+        let len = bytes.len().min(I2C_BUFFER_SIZE);
+        match self.on_receive {
+            Some(on_receive) => on_receive(&bytes[..len]),
+            None => self.client_rx.extend(&bytes[..len]),
+        }
+        len == bytes.len()
+    }
+
+    // bytes a remote host reading up to len bytes from our client address gets back, from
+    // the request handler if there is one, None if the read is NAKed
+    pub fn client_request(&self, len: usize) -> Option<Vec<u8>> {
+        self.client_address?;
+        let mut response = match self.on_request {
+            Some(on_request) => {
+                let mut buf = [0; I2C_BUFFER_SIZE];
+                let request_len = on_request(&mut buf).min(I2C_BUFFER_SIZE);
+                buf[..request_len].to_vec()
+            }
+            None => self.client_response.clone(),
+        };
+        if response.is_empty() {
+            return None;
+        }
+        response.truncate(len);
+        Some(response)
+    }
+
+    fn host_address(&mut self, address: u8) -> Result<&mut SimDevice, Error> {
+        let Some(speed) = self.host_speed else {
+            return Err(Error::Uninit);
+        };
+        if self.sda_held {
+            return Err(Error::BusBusy);
+        }
+        match self.device(address) {
+            Some(device) if device.hung => Err(Error::Timeout),
+            Some(device) if speed <= device.max_speed => Ok(device),
+            _ => Err(Error::AddressNack),
+        }
+    }
+}
+
+impl I2cBus for SimI2c {
+    fn pins_to_pull_up(&mut self) {
+        self.pulled_up = true;
+    }
+
+    fn pins_to_floating(&mut self) {
+        self.pulled_up = false;
+    }
+
+    fn host_setup(&mut self, speed: u32, rise_time_ns: u16) {
+        self.pulled_up = true;
+        self.host_speed = Some(speed);
+        self.host_rise_time_ns = rise_time_ns;
+    }
+
+    fn host_end(&mut self) {
+        self.pulled_up = false;
+        self.host_speed = None;
+    }
+
+    fn bus_recover(&mut self) -> bool {
+        self.host_speed = None;
+        self.pulled_up = true;
+        self.sda_held = self.sda_shorted;
+        !self.sda_held
+    }
+
+    fn host_ping_device(&mut self, address: u8, _direction: Direction) -> Result<bool, Error> {
+        match self.host_address(address) {
+            Ok(_) => Ok(true),
+            Err(Error::AddressNack) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn host_write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Error> {
+        let device = self.host_address(address)?;
+        if let Some((&pointer, data)) = bytes.split_first() {
+            device.pointer = pointer;
+            for &byte in data {
+                device.registers[device.pointer as usize] = byte;
+                device.pointer = device.pointer.wrapping_add(1);
+            }
+        }
+        Ok(())
+    }
+
+    fn host_read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Error> {
+        let device = self.host_address(address)?;
+        for byte in buffer {
+            *byte = device.registers[device.pointer as usize];
+            device.pointer = device.pointer.wrapping_add(1);
+        }
+        Ok(())
+    }
+
+    fn host_write_read(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Error> {
+        self.host_write(address, bytes)?;
+        self.host_read(address, buffer)
+    }
+
+    fn client_setup(&mut self, address: u8) {
+        self.pulled_up = true;
+        self.client_address = Some(address);
+    }
+
+    fn client_end(&mut self) {
+        self.pulled_up = false;
+        self.client_address = None;
+        self.client_rx.clear();
+        self.client_response.clear();
+        self.on_receive = None;
+        self.on_request = None;
+        self.sniffing = false;
+        self.sniff_events.clear();
+    }
+
+    fn client_available(&self) -> u8 {
+        self.client_rx.len().min(u8::MAX as usize) as u8
+    }
+
+    fn client_read(&mut self) -> Option<u8> {
+        self.client_rx.pop_front()
+    }
+
+    fn client_write(&mut self, bytes: &[u8]) {
+        let len = bytes.len().min(I2C_BUFFER_SIZE);
+        self.client_response = bytes[..len].to_vec();
+    }
+
+    fn client_set_handlers(
+        &mut self,
+        on_receive: Option<ClientReceiveHandler>,
+        on_request: Option<ClientRequestHandler>,
+    ) {
+        self.on_receive = on_receive;
+        self.on_request = on_request;
+    }
+
+    fn sniff_setup(&mut self, ack_writes: bool) {
+        self.pulled_up = true;
+        self.sniffing = true;
+        self.sniff_ack = ack_writes;
+        self.sniff_events.clear();
+    }
+
+    fn sniff_read(&mut self) -> Option<SniffEvent> {
+        self.sniff_events.pop_front()
+    }
+}
@@ -0,0 +1,188 @@
+// Host-side simulator for the NONIK0 firmware. The hardware-independent firmware sources
+// (modes, button debouncer, settings, RNG, utils) are compiled straight from ../rust/src,
+// while the peripherals behind the firmware's board traits are swapped for simulated ones.
+
+// shared firmware sources are written for the AVR build, not everything is used on host
+#![allow(dead_code)]
+
+#[path = "../../rust/src/adc.rs"]
+pub mod adc;
+#[path = "../../rust/src/battery.rs"]
+pub mod battery;
+#[path = "../../rust/src/board.rs"]
+pub mod board;
+#[path = "../../rust/src/i2c.rs"]
+pub mod i2c;
+#[allow(clippy::new_without_default)]
+#[path = "../../rust/src/i2c_message.rs"]
+pub mod i2c_message;
+#[path = "../../rust/src/input.rs"]
+pub mod input;
+// the firmware is linted with its pinned nightly, these lints postdate it
+#[allow(
+    clippy::collapsible_match,
+    clippy::manual_is_multiple_of,
+    clippy::new_without_default
+)]
+#[path = "../../rust/src/modes/mod.rs"]
+pub mod modes;
+// built without the music feature too, so the RTTTL parser tests always run
+#[allow(
+    clippy::absurd_extreme_comparisons,
+    clippy::manual_clamp,
+    clippy::new_without_default
+)]
+#[path = "../../rust/src/music.rs"]
+pub mod music;
+#[path = "../../rust/src/random.rs"]
+pub mod random;
+#[path = "../../rust/src/rtttl.rs"]
+pub mod rtttl;
+#[path = "../../rust/src/saved_settings.rs"]
+pub mod saved_settings;
+#[path = "../../rust/src/utils.rs"]
+pub mod utils;
+
+pub mod analog;
+pub mod buttons;
+pub mod buzzer;
+pub mod display;
+pub mod eeprom;
+mod font;
+#[cfg(not(feature = "no_i2cutils"))]
+pub mod i2c_bus;
+
+use board::{AnalogSource, Board, Buzzer};
+use buttons::{ButtonState, SimButton};
+use modes::*;
+pub use random::Rand;
+
+// same crate-level aliases the firmware's main.rs provides to the shared sources
+type DisplayPeakCurrent = hcms_29xx::PeakCurrent;
+type Eeprom = eeprom::Eeprom;
+type Event = input::InputEvent;
+type Setting = saved_settings::Setting;
+type SavedSettings = saved_settings::SavedSettings;
+
+// matches BASE_DELAY_MS in the firmware's main loop
+pub const TICK_MS: u64 = 5;
+
+// the simulated peripherals the modes run against
+pub struct SimBoard;
+
+impl Board for SimBoard {
+    type Display = display::SimDisplay;
+    type Buzzer = buzzer::SimBuzzer;
+    type Adc = analog::SimAdc;
+    #[cfg(not(feature = "no_i2cutils"))]
+    type I2c = i2c_bus::SimI2c;
+}
+
+pub struct Badge {
+    buttons: input::Buttons<SimButton, SimButton>,
+    pub left: ButtonState,
+    pub right: ButtonState,
+    pub context: Context,
+    pub peripherals: Peripherals<SimBoard>,
+    mode: Mode,
+    mode_index: usize,
+    ticks: u32,
+}
+
+impl Badge {
+    pub fn new(eeprom: Eeprom) -> Self {
+        let left = ButtonState::default();
+        let right = ButtonState::default();
+        let buttons = input::Buttons::new(left.pin(), right.pin());
+
+        let context = Context::new(SavedSettings::new(eeprom));
+        let mut peripherals = Peripherals::<SimBoard>::new(
+            analog::SimAdc::new(),
+            buzzer::SimBuzzer::new(),
+            display::SimDisplay::new(),
+            #[cfg(not(feature = "no_i2cutils"))]
+            i2c_bus::SimI2c::new(),
+        );
+
+        // apply saved display settings
+        #[cfg(not(feature = "no_settings"))]
+        {
+            let settings = Settings::new_with_settings(&context.settings);
+            settings.apply(&mut peripherals.display);
+        }
+        *peripherals.adc.settings_mut() = adc::AdcSettings::new_with_settings(&context.settings);
+
+        let mode = Mode::from_context(&context);
+        let mode_index = context.mode_index();
+        Self {
+            buttons,
+            left,
+            right,
+            context,
+            peripherals,
+            mode,
+            mode_index,
+            ticks: 0,
+        }
+    }
+
+    pub fn ticks(&self) -> u32 {
+        self.ticks
+    }
+
+    // one pass of the firmware's main loop, minus the delay
+    pub fn tick(&mut self) -> Option<Event> {
+        let event = self.buttons.update();
+        self.run_loop(&event);
+
+        self.left.tick();
+        self.right.tick();
+        event
+    }
+
+    // one pass of the main loop with the given event in place of the debounced buttons,
+    // for driving modes from a script
+    pub fn tick_with_event(&mut self, event: Option<input::InputEvent>) {
+        self.run_loop(&event);
+    }
+
+    fn run_loop(&mut self, event: &Option<Event>) {
+        match event {
+            // special case to get always get back to menu
+            Some(Event::BothHeld) if !self.context.is_menu() => {
+                self.context.to_menu();
+            }
+            // higher/shorter tone on button press
+            Some(Event::LeftPressed) | Some(Event::RightPressed)
+                if self.context.click_enabled() =>
+            {
+                self.peripherals.buzzer.tone(5000, 5);
+            }
+            // lower/longer tone on button held press
+            Some(Event::LeftHeld) | Some(Event::RightHeld) if self.context.click_enabled() => {
+                self.peripherals.buzzer.tone(4000, 10);
+            }
+            _ => {}
+        }
+
+        // change mode when requested
+        if self.mode_index != self.context.mode_index() {
+            self.mode_index = self.context.mode_index();
+            self.mode = Mode::from_context(&self.context);
+        }
+
+        self.mode
+            .update(event, &mut self.context, &mut self.peripherals);
+
+        // check the coin cell now and then, warns when it's nearly flat
+        self.context.battery_tick(&mut self.peripherals, self.mode.holds_buzzer());
+
+        // advance the song playing in the background, if any
+        #[cfg(feature = "music")]
+        self.context
+            .music
+            .tick(&mut self.peripherals.buzzer, TICK_MS as u16);
+
+        self.ticks = self.ticks.wrapping_add(1);
+    }
+}
@@ -0,0 +1,161 @@
+use crossterm::{
+    cursor,
+    event::{self, Event as TermEvent, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::Print,
+    terminal::{self, ClearType},
+};
+use nonik0_sim::{
+    buzzer::ToneEvent,
+    eeprom::Eeprom,
+    modes::{MODE_NAMES, NUM_ROWS},
+    Badge, TICK_MS,
+};
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    thread,
+    time::{Duration, Instant},
+};
+
+const BUZZER_LOG_LINES: usize = 6;
+const HELP: &str = "a/\u{2190} left  d/\u{2192} right  A/D hold  space menu  esc quit";
+
+fn main() -> io::Result<()> {
+    // optional path to persist the simulated EEPROM between runs
+    let eeprom = match std::env::args().nth(1) {
+        Some(path) => Eeprom::open(path)?,
+        None => Eeprom::new(),
+    };
+    let mut badge = Badge::new(eeprom);
+
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+
+    let result = run(&mut badge, &mut stdout);
+
+    execute!(stdout, cursor::Show, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+fn run(badge: &mut Badge, stdout: &mut impl Write) -> io::Result<()> {
+    let tick = Duration::from_millis(TICK_MS);
+    let mut next_tick = Instant::now();
+    let mut buzzer_log = VecDeque::with_capacity(BUZZER_LOG_LINES);
+    let mut last_frame = None;
+
+    loop {
+        while event::poll(Duration::ZERO)? {
+            if let TermEvent::Key(key) = event::read()? {
+                if !handle_key(badge, key) {
+                    return Ok(());
+                }
+            }
+        }
+
+        badge.tick();
+
+        let tone_events = badge.peripherals.buzzer.take_events();
+        for tone_event in &tone_events {
+            if buzzer_log.len() == BUZZER_LOG_LINES {
+                buzzer_log.pop_front();
+            }
+            buzzer_log.push_back(format_tone_event(badge.ticks(), tone_event));
+        }
+
+        let frame = badge.peripherals.display.frames();
+        if last_frame != Some(frame) || !tone_events.is_empty() {
+            last_frame = Some(frame);
+            draw(badge, &buzzer_log, stdout)?;
+        }
+
+        next_tick += tick;
+        let now = Instant::now();
+        if next_tick > now {
+            thread::sleep(next_tick - now);
+        } else {
+            next_tick = now;
+        }
+    }
+}
+
+// maps keys onto the simulated buttons, returns false when the user wants to quit
+fn handle_key(badge: &mut Badge, key: KeyEvent) -> bool {
+    if key.kind != KeyEventKind::Press {
+        return true;
+    }
+
+    match key.code {
+        KeyCode::Esc => return false,
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+        KeyCode::Char('a') | KeyCode::Left => badge.left.press(),
+        KeyCode::Char('d') | KeyCode::Right => badge.right.press(),
+        KeyCode::Char('A') => badge.left.hold(),
+        KeyCode::Char('D') => badge.right.hold(),
+        KeyCode::Char(' ') => {
+            badge.left.hold_for(nonik0_sim::buttons::BOTH_HOLD_TICKS);
+            badge.right.hold_for(nonik0_sim::buttons::BOTH_HOLD_TICKS);
+        }
+        _ => {}
+    }
+    true
+}
+
+fn format_tone_event(ticks: u32, tone_event: &ToneEvent) -> String {
+    let ms = ticks as u64 * TICK_MS;
+    match tone_event {
+        ToneEvent::Tone {
+            frequency,
+            duration: 0,
+        } => format!("{:>8}ms  tone {} Hz", ms, frequency),
+        ToneEvent::Tone {
+            frequency,
+            duration,
+        } => format!("{:>8}ms  tone {} Hz for {} ms", ms, frequency, duration),
+        ToneEvent::NoTone => format!("{:>8}ms  no tone", ms),
+    }
+}
+
+fn draw(badge: &Badge, buzzer_log: &VecDeque<String>, stdout: &mut impl Write) -> io::Result<()> {
+    let display = &badge.peripherals.display;
+    let mode_name = MODE_NAMES
+        .get(badge.context.mode_index())
+        .map(|name| String::from_utf8_lossy(&name[..]).trim().to_string())
+        .unwrap_or_default();
+    let status = format!(
+        "NONIK0 sim | {} | brightness {} | peak {}.{} mA | tone {}",
+        mode_name,
+        display.brightness(),
+        display.peak_current_ma10() / 10,
+        display.peak_current_ma10() % 10,
+        if badge.context.tone_enabled {
+            "on"
+        } else {
+            "off"
+        },
+    );
+
+    let mut lines = vec![status, String::new()];
+    for dots in display.render_rows().iter().take(NUM_ROWS) {
+        lines.push(format!("  {}", dots));
+    }
+    lines.push(String::new());
+    lines.push("buzzer:".to_string());
+    for i in 0..BUZZER_LOG_LINES {
+        lines.push(buzzer_log.get(i).cloned().unwrap_or_default());
+    }
+    lines.push(String::new());
+    lines.push(HELP.to_string());
+
+    for (row, line) in lines.iter().enumerate() {
+        queue!(
+            stdout,
+            cursor::MoveTo(0, row as u16),
+            terminal::Clear(ClearType::CurrentLine),
+            Print(line)
+        )?;
+    }
+    stdout.flush()
+}
@@ -0,0 +1,134 @@
+// Host tests for the battery curves and low-battery beep in ../rust/src/battery.rs and the
+// Vdd correction of Vdd-referenced readings
+
+use nonik0_sim::{
+    adc::{AdcChannel, AdcSettings, VDD_DEFAULT},
+    analog::SimAdc,
+    battery::{BatteryCell, LOW_FRAME, LOW_FRAME_TICKS, LOW_TONE_MS},
+    board::{AnalogSource, DisplaySink},
+    buzzer::ToneEvent,
+    display::SimDisplay,
+    eeprom::Eeprom,
+    Badge,
+};
+
+#[test]
+fn full_and_empty_at_the_curve_ends() {
+    assert_eq!(BatteryCell::Lir2032.percent(420), 100);
+    assert_eq!(BatteryCell::Lir2032.percent(450), 100);
+    assert_eq!(BatteryCell::Lir2032.percent(330), 0);
+    assert_eq!(BatteryCell::Lir2032.percent(300), 0);
+    assert_eq!(BatteryCell::Cr2032.percent(300), 100);
+    assert_eq!(BatteryCell::Cr2032.percent(220), 0);
+    assert_eq!(BatteryCell::Cr2032.percent(0), 0);
+}
+
+#[test]
+fn exact_on_the_curve_points() {
+    assert_eq!(BatteryCell::Lir2032.percent(410), 90);
+    assert_eq!(BatteryCell::Lir2032.percent(370), 40);
+    assert_eq!(BatteryCell::Lir2032.percent(350), 8);
+    assert_eq!(BatteryCell::Cr2032.percent(280), 60);
+    assert_eq!(BatteryCell::Cr2032.percent(240), 8);
+}
+
+#[test]
+fn linear_between_the_curve_points() {
+    // halfway between (380, 55) and (390, 68), rounded down
+    assert_eq!(BatteryCell::Lir2032.percent(385), 61);
+    // (330, 0) to (350, 8) is the widest step
+    assert_eq!(BatteryCell::Lir2032.percent(340), 4);
+    assert_eq!(BatteryCell::Lir2032.percent(349), 7);
+    assert_eq!(BatteryCell::Cr2032.percent(255), 20);
+    assert_eq!(BatteryCell::Cr2032.percent(299), 98);
+}
+
+#[test]
+fn percent_never_goes_up_as_vdd_drops() {
+    for cell in [BatteryCell::Lir2032, BatteryCell::Cr2032] {
+        let mut last = 100;
+        for vdd in (0..=450).rev() {
+            let percent = cell.percent(vdd);
+            assert!(percent <= last, "{vdd}: {percent} after {last}");
+            last = percent;
+        }
+    }
+}
+
+#[test]
+fn vdd_referenced_readings_follow_the_measured_vdd() {
+    let mut adc = SimAdc::new();
+    *adc.settings_mut() = AdcSettings::VDD_REFERENCED;
+    adc.apply_settings();
+    adc.set_supply(300);
+    adc.set_voltage(AdcChannel::Vsda, 150);
+
+    // still scaled by the default Vdd, so a lower supply reads high
+    assert_eq!(adc.vdd(), VDD_DEFAULT);
+    let stale = adc.read_voltage_nonblocking(AdcChannel::Vsda).unwrap();
+    assert!(stale > 170, "{stale}");
+
+    assert_eq!(adc.read_vdd(), 300);
+    let level = adc.read_voltage_nonblocking(AdcChannel::Vsda).unwrap();
+    assert!(level.abs_diff(150) <= 1, "{level}");
+}
+
+#[test]
+fn vref_reads_back_as_1v1() {
+    let mut adc = SimAdc::new();
+    adc.apply_settings();
+    let vref = adc.read_voltage_nonblocking(AdcChannel::Vref).unwrap();
+    assert!(vref.abs_diff(110) <= 1, "{vref}");
+}
+
+fn flat_cell_badge(tone_enabled: bool) -> Badge {
+    let mut badge = Badge::new(Eeprom::new());
+    badge.context.tone_enabled = tone_enabled;
+    badge.peripherals.adc.set_supply(300);
+    badge
+}
+
+#[test]
+fn flat_cell_beeps_on_the_first_check() {
+    let mut badge = flat_cell_badge(true);
+    badge.tick_with_event(None);
+    assert_eq!(
+        badge.peripherals.buzzer.take_events(),
+        [ToneEvent::Tone {
+            frequency: 2000,
+            duration: LOW_TONE_MS as u32,
+        }]
+    );
+
+    // not again until the next check
+    badge.tick_with_event(None);
+    assert!(badge.peripherals.buzzer.take_events().is_empty());
+}
+
+fn low_frame() -> [u8; 40] {
+    let mut display = SimDisplay::new();
+    display.print_ascii_bytes(LOW_FRAME).unwrap();
+    *display.cols()
+}
+
+#[test]
+fn flat_cell_shows_a_warning_with_tones_off() {
+    let mut badge = flat_cell_badge(false);
+    badge.tick_with_event(None);
+    assert!(badge.peripherals.buzzer.take_events().is_empty());
+    assert_eq!(*badge.peripherals.display.cols(), low_frame());
+
+    // stays up over the mode's own frames for a while, then the mode redraws
+    badge.peripherals.display.start_recording();
+    for _ in 1..LOW_FRAME_TICKS {
+        badge.tick_with_event(None);
+    }
+    assert!(badge
+        .peripherals
+        .display
+        .take_recording()
+        .iter()
+        .all(|cols| *cols == low_frame()));
+    badge.tick_with_event(None);
+    assert_ne!(*badge.peripherals.display.cols(), low_frame());
+}
@@ -0,0 +1,303 @@
+// Golden-frame regression tests. Each mode is driven with a scripted sequence of input
+// events and every frame it pushes to the display is compared against the stored
+// rendering in tests/golden/<mode>.txt. The RNG is seeded the same way for every run, so
+// the animated modes are deterministic too.
+//
+// After an intended change to a mode's output, regenerate the files with:
+//
+//     GOLDEN_BLESS=1 cargo test --test golden
+
+use hcms_29xx::CHAR_WIDTH;
+use nonik0_sim::{
+    eeprom::Eeprom,
+    input::InputEvent::{self, *},
+    modes::{MODE_NAMES, NUM_CHARS, NUM_COLS, NUM_ROWS},
+    Badge, Rand,
+};
+use std::{fmt::Write, fs, path::PathBuf, sync::Mutex};
+
+const SEED: u32 = 0x4E4F_4E49;
+
+// the RNG state is global, so tests that depend on it can't run in parallel
+static RAND_LOCK: Mutex<()> = Mutex::new(());
+
+// events delivered at the given tick, all other ticks get no event
+type Script = &'static [(u32, InputEvent)];
+
+fn render_frame(out: &mut String, tick: u32, cols: &[u8; NUM_COLS]) {
+    writeln!(out, "tick {}", tick).unwrap();
+    for row in 0..NUM_ROWS {
+        for (char_index, char_cols) in cols.chunks(CHAR_WIDTH).enumerate() {
+            if char_index > 0 {
+                out.push(' ');
+            }
+            for col in char_cols {
+                out.push(if col & (1 << row) != 0 { '#' } else { '.' });
+            }
+        }
+        out.push('\n');
+    }
+}
+
+fn record(mode_name: Option<&[u8; NUM_CHARS]>, ticks: u32, script: Script) -> String {
+    record_with(mode_name, ticks, script, |_| {})
+}
+
+// same as record, with a chance to set up the badge (e.g. devices on the bus) first
+fn record_with(
+    mode_name: Option<&[u8; NUM_CHARS]>,
+    ticks: u32,
+    script: Script,
+    setup: impl FnOnce(&mut Badge),
+) -> String {
+    let _lock = RAND_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    Rand::seed(SEED);
+
+    let mut badge = Badge::new(Eeprom::new());
+    setup(&mut badge);
+    match mode_name {
+        Some(name) => {
+            let index = MODE_NAMES.iter().position(|n| *n == name).unwrap();
+            badge.context.to_mode(index);
+        }
+        None => badge.context.to_menu(),
+    }
+    badge.peripherals.display.start_recording();
+
+    let mut out = String::new();
+    for tick in 0..ticks {
+        let event = script.iter().find(|(t, _)| *t == tick).map(|(_, e)| *e);
+        badge.tick_with_event(event);
+        for cols in badge.peripherals.display.take_recording() {
+            render_frame(&mut out, tick, &cols);
+        }
+    }
+    out
+}
+
+fn check(golden: &str, actual: String) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.txt", golden));
+
+    if std::env::var_os("GOLDEN_BLESS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("can't read {}: {}", path.display(), e));
+    if expected == actual {
+        return;
+    }
+
+    // report the first frame that differs instead of dumping both files
+    let mut frame = "start";
+    for (line, (e, a)) in expected.lines().zip(actual.lines()).enumerate() {
+        if e.starts_with("tick") {
+            frame = e;
+        }
+        if e != a {
+            panic!(
+                "{} differs from {} at line {} ({}):\n expected: {}\n   actual: {}",
+                golden,
+                path.display(),
+                line + 1,
+                frame,
+                e,
+                a
+            );
+        }
+    }
+    panic!(
+        "{} has {} lines, expected {} ({})",
+        golden,
+        actual.lines().count(),
+        expected.lines().count(),
+        path.display()
+    );
+}
+
+#[test]
+fn menu() {
+    const SCRIPT: Script = &[
+        (10, RightReleased),
+        (20, RightReleased),
+        (30, LeftReleased),
+        (40, LeftReleased),
+        (50, LeftReleased),
+        (60, RightReleased),
+        (70, RightHeld),
+    ];
+    check("menu", record(None, 100, SCRIPT));
+}
+
+#[test]
+#[cfg(not(feature = "no_nametag"))]
+fn nametag() {
+    const SCRIPT: Script = &[
+        (10, RightHeld),
+        (20, RightReleased),
+        (30, RightReleased),
+        (40, RightHeld),
+        (50, LeftReleased),
+        (60, RightHeld),
+        (70, LeftHeld),
+        (80, LeftHeld),
+        (90, LeftHeld),
+    ];
+    check("nametag", record(Some(b" Nametag"), 120, SCRIPT));
+}
+
+#[test]
+#[cfg(not(feature = "no_i2cutils"))]
+fn i2c_utils() {
+    const SCRIPT: Script = &[(300, RightReleased), (400, RightHeld), (500, RightHeld)];
+    check("i2c_utils", record(Some(b"I2C Util"), 600, SCRIPT));
+}
+
+#[test]
+#[cfg(not(feature = "no_i2cutils"))]
+fn i2c_peek() {
+    // stopped on 0x10: open it, step to register 1, edit and write it, go back to
+    // scanning, which stops on 0x11 right away, and leave from there
+    const SCRIPT: Script = &[
+        (40, RightHeld),
+        (50, RightReleased),
+        (60, RightHeld),
+        (70, RightReleased),
+        (80, RightHeld),
+        (90, LeftHeld),
+        (100, LeftHeld),
+    ];
+    let setup = |badge: &mut Badge| {
+        let i2c = &mut badge.peripherals.i2c;
+        i2c.add_device(0x10);
+        i2c.add_device(0x11);
+        i2c.device_registers(0x10).unwrap()[..2].copy_from_slice(&[0x12, 0x34]);
+    };
+    check(
+        "i2c_peek",
+        record_with(Some(b"I2C Util"), 120, SCRIPT, setup),
+    );
+}
+
+#[test]
+#[cfg(feature = "music")]
+fn jukebox() {
+    const SCRIPT: Script = &[
+        (40, RightReleased),
+        (80, LeftReleased),
+        (100, RightHeld),
+        (120, RightReleased),
+        (300, RightReleased),
+        (350, RightReleased),
+        (500, LeftReleased),
+        (550, RightHeld),
+        (570, RightReleased),
+        (590, RightHeld),
+        (610, LeftReleased),
+    ];
+    check("jukebox", record(Some(b" Jukebox"), 650, SCRIPT));
+}
+
+#[test]
+#[cfg(not(feature = "no_link"))]
+fn link() {
+    const SCRIPT: Script = &[(100, RightHeld), (150, RightReleased), (200, RightHeld)];
+    check("link", record(Some(b"    Link"), 250, SCRIPT));
+}
+
+#[test]
+#[cfg(not(feature = "no_random"))]
+fn random() {
+    const SCRIPT: Script = &[
+        (10, RightPressed),
+        (20, LeftPressed),
+        (30, RightHeld),
+        (40, RightPressed),
+        (50, RightPressed),
+        (60, RightHeld),
+        (70, RightPressed),
+        (80, RightHeld),
+        (90, LeftPressed),
+        (100, LeftHeld),
+        (110, RightPressed),
+    ];
+    check("random", record(Some(b"  Random"), 150, SCRIPT));
+}
+
+#[test]
+#[cfg(not(feature = "no_sensors"))]
+fn sensors() {
+    const SCRIPT: Script = &[
+        (50, RightReleased),
+        (100, RightReleased),
+        (150, RightReleased),
+        (200, LeftReleased),
+        (250, RightHeld),
+        (300, RightReleased),
+        (350, RightHeld),
+    ];
+    check("sensors", record(Some(b" Sensors"), 400, SCRIPT));
+}
+
+#[test]
+#[cfg(not(feature = "no_settings"))]
+fn settings() {
+    const SCRIPT: Script = &[
+        (10, LeftReleased),
+        (20, LeftReleased),
+        (30, RightReleased),
+        (40, RightHeld),
+        (50, RightReleased),
+        (60, RightHeld),
+        (70, LeftReleased),
+        (80, RightHeld),
+        (90, RightReleased),
+    ];
+    check("settings", record(Some(b"Settings"), 120, SCRIPT));
+}
+
+#[test]
+#[cfg(not(feature = "no_traffic"))]
+fn traffic() {
+    const SCRIPT: Script = &[
+        (40, LeftReleased),
+        (80, LeftReleased),
+        (120, RightReleased),
+        (200, LeftReleased),
+        (260, RightReleased),
+        (600, RightHeld),
+    ];
+    check("traffic", record(Some(b" Traffic"), 700, SCRIPT));
+}
+
+#[test]
+#[cfg(not(feature = "no_tunnel"))]
+fn tunnel() {
+    const SCRIPT: Script = &[
+        (40, LeftReleased),
+        (80, LeftReleased),
+        (120, RightReleased),
+        (200, LeftReleased),
+        (260, RightReleased),
+        (600, RightHeld),
+    ];
+    check("tunnel", record(Some(b"  Tunnel"), 700, SCRIPT));
+}
+
+#[test]
+#[cfg(not(feature = "no_vibes"))]
+fn vibes() {
+    const SCRIPT: Script = &[
+        (100, RightReleased),
+        (150, RightReleased),
+        (200, LeftReleased),
+        (250, RightHeld),
+        (350, LeftReleased),
+        (400, RightReleased),
+    ];
+    check("vibes", record(Some(b"   Vibes"), 500, SCRIPT));
+}
@@ -0,0 +1,276 @@
+// Host tests for the chunked I2C message framing in ../rust/src/i2c_message.rs and its use
+// by the I2C Util client (registers 0x09 and, with the music feature, 0x0C), and for the
+// client's mode register (0x07)
+
+use nonik0_sim::{
+    eeprom::Eeprom,
+    i2c_message::{ChunkError, MessageAssembler, CHUNK_FIRST, CHUNK_LAST},
+    modes::MODE_NAMES,
+    saved_settings::Setting,
+    Badge,
+};
+
+const MESSAGE: u8 = 0x01;
+const MODE: u8 = 0x07;
+const MESSAGE_CHUNK: u8 = 0x09;
+#[cfg(feature = "music")]
+const SONG: u8 = 0x0C;
+
+fn first(sequence: u8) -> u8 {
+    CHUNK_FIRST | sequence
+}
+
+fn last(sequence: u8) -> u8 {
+    CHUNK_LAST | sequence
+}
+
+#[test]
+fn single_chunk() {
+    let mut assembler = MessageAssembler::new();
+    let mut buf = [0u8; 16];
+    assert_eq!(
+        assembler.push(CHUNK_FIRST | CHUNK_LAST, b"hello", &mut buf),
+        Ok(Some(5))
+    );
+    assert_eq!(&buf[..5], b"hello");
+    assert_eq!(assembler.next_sequence(), None);
+}
+
+#[test]
+fn multiple_chunks() {
+    let mut assembler = MessageAssembler::new();
+    let mut buf = [0u8; 16];
+    assert_eq!(assembler.push(first(0), b"abc", &mut buf), Ok(None));
+    assert_eq!(assembler.next_sequence(), Some(1));
+    assert_eq!(assembler.push(1, b"def", &mut buf), Ok(None));
+    assert_eq!(assembler.push(last(2), b"gh", &mut buf), Ok(Some(8)));
+    assert_eq!(&buf[..8], b"abcdefgh");
+}
+
+#[test]
+fn sequence_wraps() {
+    let mut assembler = MessageAssembler::new();
+    let mut buf = [0u8; 16];
+    assert_eq!(assembler.push(first(62), b"a", &mut buf), Ok(None));
+    assert_eq!(assembler.push(63, b"b", &mut buf), Ok(None));
+    assert_eq!(assembler.push(last(0), b"c", &mut buf), Ok(Some(3)));
+    assert_eq!(&buf[..3], b"abc");
+}
+
+#[test]
+fn not_started() {
+    let mut assembler = MessageAssembler::new();
+    let mut buf = [0u8; 16];
+    assert_eq!(
+        assembler.push(0, b"abc", &mut buf),
+        Err(ChunkError::NotStarted)
+    );
+    assert_eq!(
+        assembler.push(last(1), b"abc", &mut buf),
+        Err(ChunkError::NotStarted)
+    );
+
+    // a finished message needs a new first chunk too
+    assembler
+        .push(CHUNK_FIRST | CHUNK_LAST, b"x", &mut buf)
+        .unwrap();
+    assert_eq!(
+        assembler.push(1, b"y", &mut buf),
+        Err(ChunkError::NotStarted)
+    );
+}
+
+#[test]
+fn out_of_sequence() {
+    let mut assembler = MessageAssembler::new();
+    let mut buf = [0u8; 16];
+    assembler.push(first(0), b"abc", &mut buf).unwrap();
+    assert_eq!(
+        assembler.push(2, b"ghi", &mut buf),
+        Err(ChunkError::OutOfSequence)
+    );
+
+    // the partial message is gone, the right chunk is too late now
+    assert_eq!(
+        assembler.push(last(1), b"def", &mut buf),
+        Err(ChunkError::NotStarted)
+    );
+}
+
+#[test]
+fn duplicate_chunk_is_ignored() {
+    // host didn't see our ACK and sent the same chunk again
+    let mut assembler = MessageAssembler::new();
+    let mut buf = [0u8; 16];
+    assembler.push(first(5), b"abc", &mut buf).unwrap();
+    assembler.push(6, b"def", &mut buf).unwrap();
+    assert_eq!(assembler.push(6, b"def", &mut buf), Ok(None));
+    assert_eq!(assembler.push(last(7), b"g", &mut buf), Ok(Some(7)));
+    assert_eq!(&buf[..7], b"abcdefg");
+}
+
+#[test]
+fn overflow() {
+    let mut assembler = MessageAssembler::new();
+    let mut buf = [0u8; 8];
+    assembler.push(first(0), b"abcde", &mut buf).unwrap();
+    assert_eq!(
+        assembler.push(last(1), b"fghi", &mut buf),
+        Err(ChunkError::Overflow)
+    );
+    assert_eq!(assembler.next_sequence(), None);
+
+    // exactly full is fine
+    assembler.push(first(0), b"abcde", &mut buf).unwrap();
+    assert_eq!(assembler.push(last(1), b"fgh", &mut buf), Ok(Some(8)));
+    assert_eq!(&buf, b"abcdefgh");
+}
+
+#[test]
+fn first_chunk_restarts() {
+    let mut assembler = MessageAssembler::new();
+    let mut buf = [0u8; 16];
+    assembler.push(first(0), b"stale", &mut buf).unwrap();
+    assert_eq!(assembler.push(first(9), b"new", &mut buf), Ok(None));
+    assert_eq!(assembler.push(last(10), b"er", &mut buf), Ok(Some(5)));
+    assert_eq!(&buf[..5], b"newer");
+}
+
+// Badge running the I2C Util message client
+fn client_badge() -> Badge {
+    let mut badge = Badge::new(Eeprom::new());
+    badge
+        .context
+        .settings
+        .save_setting_byte(Setting::I2CPage, 1);
+    let index = MODE_NAMES.iter().position(|n| *n == b"I2C Util").unwrap();
+    badge.context.to_mode(index);
+    badge.tick();
+    assert!(badge.peripherals.i2c.client_address().is_some());
+    badge
+}
+
+// host write, retried like a real host would after a NAK
+fn write(badge: &mut Badge, bytes: &[u8]) {
+    for _ in 0..10 {
+        if badge.peripherals.i2c.client_push(bytes) {
+            badge.tick();
+            return;
+        }
+        badge.tick();
+    }
+    panic!("client kept NAKing");
+}
+
+fn read(badge: &mut Badge, register: u8, len: usize) -> Vec<u8> {
+    write(badge, &[register]);
+    badge.peripherals.i2c.client_request(len).unwrap()
+}
+
+#[test]
+fn client_reassembles_long_message() {
+    let mut badge = client_badge();
+    assert_eq!(read(&mut badge, MESSAGE_CHUNK, 1), [CHUNK_FIRST]);
+
+    let text: Vec<u8> = (0..100).map(|i| b'a' + i % 26).collect();
+    let chunks: Vec<&[u8]> = text.chunks(30).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let mut header = i as u8;
+        if i == 0 {
+            header |= CHUNK_FIRST;
+        }
+        if i == chunks.len() - 1 {
+            header |= CHUNK_LAST;
+        }
+        let mut bytes = vec![MESSAGE_CHUNK, header];
+        bytes.extend_from_slice(chunk);
+        write(&mut badge, &bytes);
+
+        let expected = if i == chunks.len() - 1 {
+            CHUNK_FIRST
+        } else {
+            i as u8 + 1
+        };
+        assert_eq!(
+            badge.peripherals.i2c.client_request(1),
+            Some(vec![expected])
+        );
+    }
+
+    // a single register read is limited to one transfer
+    assert_eq!(read(&mut badge, MESSAGE, 32), text[..32]);
+}
+
+#[test]
+fn client_reports_rejected_chunk() {
+    let mut badge = client_badge();
+    write(&mut badge, &[MESSAGE_CHUNK, first(0), b'a']);
+    write(&mut badge, &[MESSAGE_CHUNK, 3, b'b']);
+    assert_eq!(badge.peripherals.i2c.client_request(1), Some(vec![0xFF]));
+
+    // the next first chunk starts over
+    write(
+        &mut badge,
+        &[MESSAGE_CHUNK, CHUNK_FIRST | CHUNK_LAST, b'o', b'k', 0],
+    );
+    assert_eq!(
+        badge.peripherals.i2c.client_request(1),
+        Some(vec![CHUNK_FIRST])
+    );
+    assert_eq!(read(&mut badge, MESSAGE, 8), b"ok");
+}
+
+#[test]
+fn client_mode_register() {
+    let mut badge = client_badge();
+    let own = badge.context.mode_index() as u8;
+    assert_eq!(read(&mut badge, MODE, 1), [own]);
+
+    // its own index keeps the client answering
+    write(&mut badge, &[MODE, own]);
+    assert!(badge.peripherals.i2c.client_address().is_some());
+    assert_eq!(read(&mut badge, MODE, 1), [own]);
+
+    // 0 goes back to the menu like any other mode switch
+    write(&mut badge, &[MODE, 0]);
+    assert!(badge.context.is_menu());
+    assert!(badge.peripherals.i2c.client_address().is_none());
+}
+
+#[test]
+#[cfg(feature = "music")]
+fn client_plays_rtttl_song() {
+    use nonik0_sim::{buzzer::ToneEvent, music::get_note_frequency};
+
+    let mut badge = client_badge();
+    let song = b"Scale:d=4,o=5,b=120:c,d,e,f,g,a,b,c6";
+    let (head, tail) = song.split_at(30);
+    write(&mut badge, &[&[SONG, first(0)], head].concat());
+    write(&mut badge, &[&[SONG, last(1)], tail].concat());
+    assert_eq!(
+        badge.peripherals.i2c.client_request(1),
+        Some(vec![CHUNK_FIRST])
+    );
+
+    // playing with the ringtone's own octave adjust, the message shows its name
+    assert!(badge.context.music.is_playing());
+    let octave = 5 + badge.context.music.octave_adjust as u8;
+    let events = badge.peripherals.buzzer.take_events();
+    assert!(matches!(
+        events.first(),
+        Some(ToneEvent::Tone { frequency, .. }) if *frequency == get_note_frequency(0, octave)
+    ));
+    assert_eq!(read(&mut badge, MESSAGE, 32), b"Scale");
+}
+
+#[test]
+#[cfg(feature = "music")]
+fn client_rejects_bad_rtttl_song() {
+    let mut badge = client_badge();
+    write(
+        &mut badge,
+        &[SONG, CHUNK_FIRST | CHUNK_LAST, b'x', b':', b'x'],
+    );
+    assert_eq!(badge.peripherals.i2c.client_request(1), Some(vec![0xFF]));
+    assert!(!badge.context.music.is_playing());
+}
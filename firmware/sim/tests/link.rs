@@ -0,0 +1,84 @@
+// Host tests for name exchanges in ../rust/src/modes/link.rs, with the other badge played
+// by the simulated bus
+
+use nonik0_sim::{eeprom::Eeprom, modes::MODE_NAMES, saved_settings::SavedSettings, Badge};
+
+const LINK_ADDRESS: u8 = 0x2B;
+const LINK_HELLO: u8 = 0x01;
+const LINK_ID: u8 = 0xA7;
+const OUR_NAME: &[u8; 8] = b" NONIK0 ";
+
+fn link_badge() -> Badge {
+    let mut badge = Badge::new(Eeprom::new());
+    let index = MODE_NAMES.iter().position(|n| *n == b"    Link").unwrap();
+    badge.context.to_mode(index);
+    badge.tick();
+    assert_eq!(badge.peripherals.i2c.client_address(), Some(LINK_ADDRESS));
+    badge
+}
+
+fn newest_contact(settings: &SavedSettings) -> Option<[u8; 8]> {
+    let mut buf = [0; 8];
+    settings.read_contact(0, &mut buf).then_some(buf)
+}
+
+#[test]
+fn answers_a_calling_badge() {
+    let mut badge = link_badge();
+
+    // the other badge calls first: it writes its hello and reads our name back
+    let mut hello = vec![LINK_HELLO];
+    hello.extend_from_slice(b"BOB     ");
+    assert!(badge.peripherals.i2c.client_push(&hello));
+    let mut id = vec![LINK_ID];
+    id.extend_from_slice(OUR_NAME);
+    assert_eq!(badge.peripherals.i2c.client_request(9), Some(id));
+    badge.tick();
+
+    assert_eq!(newest_contact(&badge.context.settings), Some(*b"BOB     "));
+}
+
+#[test]
+fn calls_a_listening_badge() {
+    let mut badge = link_badge();
+
+    // the other badge is listening: our hello lands from register 1, the read that follows
+    // in the same transaction continues after it
+    badge.peripherals.i2c.add_device(LINK_ADDRESS);
+    let registers = badge
+        .peripherals
+        .i2c
+        .device_registers(LINK_ADDRESS)
+        .unwrap();
+    registers[9] = LINK_ID;
+    registers[10..18].copy_from_slice(b"CAROL   ");
+
+    // the call comes after a random wait of at most 100 ticks
+    for _ in 0..120 {
+        badge.tick();
+    }
+    assert_eq!(newest_contact(&badge.context.settings), Some(*b"CAROL   "));
+    let registers = badge
+        .peripherals
+        .i2c
+        .device_registers(LINK_ADDRESS)
+        .unwrap();
+    assert_eq!(&registers[1..9], OUR_NAME);
+
+    // linked, so it stops calling and keeps its one contact
+    for _ in 0..200 {
+        badge.tick();
+    }
+    let mut buf = [0; 8];
+    assert!(!badge.context.settings.read_contact(1, &mut buf));
+}
+
+#[test]
+fn ignores_a_garbled_name() {
+    let mut badge = link_badge();
+    let mut hello = vec![LINK_HELLO];
+    hello.extend_from_slice(b"B\x00B\xFF    ");
+    assert!(badge.peripherals.i2c.client_push(&hello));
+    badge.tick();
+    assert_eq!(newest_contact(&badge.context.settings), None);
+}
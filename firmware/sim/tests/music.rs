@@ -0,0 +1,311 @@
+// Host tests for the background music sequencer in ../rust/src/music.rs and the Jukebox
+// mode driving it
+
+use nonik0_sim::{
+    buzzer::{SimBuzzer, ToneEvent},
+    music::{get_note_frequency, n, pause, PlayState, Sequencer, AN, CN, EN, END, SONGS},
+    rtttl,
+};
+
+const TIMING_UNIT_MS: u8 = 10;
+const GAP_MS: u16 = TIMING_UNIT_MS as u16 / 2;
+
+static SONG: [u16; 4] = [n(CN, 4, 4), pause(2), n(EN, 4, 8), END];
+
+// eighth notes are 8 timing units, 15ms each at 240 bpm, and octave 4 is moved up by 3
+const RTTTL_SONG: &[u8] = b"Up:d=8,o=4,b=240:c,e";
+
+fn sequencer() -> Sequencer {
+    let mut music = Sequencer::new();
+    music.timing_unit_ms = TIMING_UNIT_MS;
+    music.octave_adjust = 0;
+    music
+}
+
+fn tone(note: u8, octave: u8, timing: u8) -> ToneEvent {
+    ToneEvent::Tone {
+        frequency: get_note_frequency(note, octave),
+        duration: timing as u32 * TIMING_UNIT_MS as u32,
+    }
+}
+
+// ticks in steps of step_ms until the buzzer gets a request, returns it and the time taken
+fn run_until_event(
+    music: &mut Sequencer,
+    buzzer: &mut SimBuzzer,
+    step_ms: u16,
+) -> (ToneEvent, u16) {
+    let mut elapsed = 0;
+    for _ in 0..1000 {
+        music.tick(buzzer, step_ms);
+        elapsed += step_ms;
+        if let Some(&event) = buzzer.take_events().first() {
+            return (event, elapsed);
+        }
+    }
+    panic!("no buzzer request");
+}
+
+#[test]
+fn plays_notes_with_gaps() {
+    let mut music = sequencer();
+    let mut buzzer = SimBuzzer::new();
+    music.play(&SONG);
+
+    music.tick(&mut buzzer, 5);
+    assert_eq!(buzzer.take_events(), [tone(CN, 4, 4)]);
+    assert_eq!(music.current_note(), Some((CN, 4)));
+
+    // the pause is silence after the first note and its gap
+    let (event, elapsed) = run_until_event(&mut music, &mut buzzer, 1);
+    assert_eq!(event, ToneEvent::NoTone);
+    assert_eq!(elapsed, 4 * TIMING_UNIT_MS as u16 + GAP_MS);
+    assert_eq!(music.current_note(), None);
+
+    let (event, elapsed) = run_until_event(&mut music, &mut buzzer, 1);
+    assert_eq!(event, tone(EN, 4, 8));
+    assert_eq!(elapsed, 2 * TIMING_UNIT_MS as u16 + GAP_MS);
+    assert_eq!(music.position(), 3);
+}
+
+#[test]
+fn octave_adjust_shifts_notes() {
+    let mut music = sequencer();
+    let mut buzzer = SimBuzzer::new();
+    music.octave_adjust = 1;
+    music.play(&SONG);
+    music.tick(&mut buzzer, 5);
+    assert_eq!(buzzer.take_events(), [tone(CN, 5, 4)]);
+}
+
+#[test]
+fn stops_at_end_of_song() {
+    let mut music = sequencer();
+    let mut buzzer = SimBuzzer::new();
+    music.play(&SONG);
+
+    for _ in 0..100 {
+        music.tick(&mut buzzer, 5);
+    }
+    assert_eq!(music.state(), PlayState::Stopped);
+    assert_eq!(buzzer.take_events().last(), Some(&ToneEvent::NoTone));
+    assert_eq!(music.current_note(), None);
+
+    // nothing more once stopped
+    music.tick(&mut buzzer, 5);
+    assert!(buzzer.take_events().is_empty());
+}
+
+#[test]
+fn pause_and_resume() {
+    let mut music = sequencer();
+    let mut buzzer = SimBuzzer::new();
+    music.play(&SONG);
+    music.tick(&mut buzzer, 5);
+    buzzer.take_events();
+
+    music.pause(&mut buzzer);
+    assert_eq!(music.state(), PlayState::Paused);
+    assert!(!music.is_playing());
+    assert_eq!(buzzer.take_events(), [ToneEvent::NoTone]);
+
+    // time doesn't move while paused
+    for _ in 0..100 {
+        music.tick(&mut buzzer, 5);
+    }
+    assert!(buzzer.take_events().is_empty());
+    assert_eq!(music.position(), 1);
+
+    // picks up with the note after the one that was cut off
+    music.resume();
+    assert!(music.is_playing());
+    music.tick(&mut buzzer, 5);
+    assert_eq!(buzzer.take_events(), [ToneEvent::NoTone]);
+    assert_eq!(music.position(), 2);
+}
+
+#[test]
+fn hold_restarts_cut_off_note() {
+    let mut music = sequencer();
+    let mut buzzer = SimBuzzer::new();
+    music.play(&SONG);
+    music.tick(&mut buzzer, 5);
+    buzzer.take_events();
+
+    // the first note is still sounding, so it plays again once the other tone is over
+    music.hold(100);
+    let (event, elapsed) = run_until_event(&mut music, &mut buzzer, 1);
+    assert_eq!(event, tone(CN, 4, 4));
+    assert_eq!(elapsed, 100);
+    assert_eq!(music.position(), 1);
+
+    // in the gap after a note only the next one waits
+    let (event, _) = run_until_event(&mut music, &mut buzzer, 1);
+    assert_eq!(event, ToneEvent::NoTone);
+    let (event, _) = run_until_event(&mut music, &mut buzzer, 1);
+    assert_eq!(event, tone(EN, 4, 8));
+    for _ in 0..8 * TIMING_UNIT_MS + 1 {
+        music.tick(&mut buzzer, 1);
+    }
+    music.hold(100);
+    let (event, elapsed) = run_until_event(&mut music, &mut buzzer, 1);
+    assert_eq!(event, ToneEvent::NoTone);
+    assert_eq!(elapsed, 100);
+    assert_eq!(music.state(), PlayState::Stopped);
+}
+
+#[test]
+fn hold_twice_replays_the_note_once() {
+    let mut music = sequencer();
+    let mut buzzer = SimBuzzer::new();
+    music.play(&SONG);
+    music.tick(&mut buzzer, 5);
+    buzzer.take_events();
+
+    // the second tone comes while the first still holds the buzzer, the note that was cut
+    // off is still the one to play again
+    music.hold(100);
+    music.tick(&mut buzzer, 50);
+    music.hold(100);
+    let (event, elapsed) = run_until_event(&mut music, &mut buzzer, 1);
+    assert_eq!(event, tone(CN, 4, 4));
+    assert_eq!(elapsed, 100);
+    assert_eq!(music.position(), 1);
+
+    let (event, _) = run_until_event(&mut music, &mut buzzer, 1);
+    assert_eq!(event, ToneEvent::NoTone);
+    assert_eq!(music.position(), 2);
+}
+
+#[test]
+fn stop_silences_and_play_restarts() {
+    let mut music = sequencer();
+    let mut buzzer = SimBuzzer::new();
+    music.play(&SONG);
+    music.tick(&mut buzzer, 5);
+    buzzer.take_events();
+
+    music.stop(&mut buzzer);
+    assert_eq!(music.state(), PlayState::Stopped);
+    assert_eq!(buzzer.take_events(), [ToneEvent::NoTone]);
+    music.tick(&mut buzzer, 5);
+    assert!(buzzer.take_events().is_empty());
+
+    // stopping again doesn't touch the buzzer
+    music.stop(&mut buzzer);
+    assert!(buzzer.take_events().is_empty());
+
+    music.play(&SONG);
+    music.tick(&mut buzzer, 5);
+    assert_eq!(buzzer.take_events(), [tone(CN, 4, 4)]);
+    assert_eq!(music.position(), 1);
+}
+
+#[test]
+fn high_notes_clamp_to_silence() {
+    static HIGH: [u16; 2] = [n(AN, 8, 4), END];
+    let mut music = sequencer();
+    let mut buzzer = SimBuzzer::new();
+    music.octave_adjust = 1;
+    music.play(&HIGH);
+    music.tick(&mut buzzer, 5);
+    assert_eq!(buzzer.take_events(), [ToneEvent::NoTone]);
+}
+
+#[test]
+fn plays_rtttl_from_own_buffer() {
+    let mut music = sequencer();
+    let mut buzzer = SimBuzzer::new();
+    assert_eq!(music.play_rtttl(RTTTL_SONG), Ok(()));
+    assert_eq!(music.timing_unit_ms, 15);
+    assert_eq!(music.octave_adjust, 3);
+
+    music.tick(&mut buzzer, 5);
+    let c7 = ToneEvent::Tone {
+        frequency: get_note_frequency(CN, 7),
+        duration: 120,
+    };
+    assert_eq!(buzzer.take_events(), [c7]);
+    assert_eq!(music.current_note(), Some((CN, 4)));
+
+    let (event, _) = run_until_event(&mut music, &mut buzzer, 5);
+    let e7 = ToneEvent::Tone {
+        frequency: get_note_frequency(EN, 7),
+        duration: 120,
+    };
+    assert_eq!(event, e7);
+}
+
+#[test]
+fn bad_rtttl_ends_song_in_buffer() {
+    let mut music = sequencer();
+    let mut buzzer = SimBuzzer::new();
+    music.play_rtttl(RTTTL_SONG).unwrap();
+    music.tick(&mut buzzer, 5);
+
+    assert_eq!(
+        music.play_rtttl(b"Bad:d=4:x"),
+        Err(rtttl::Error::InvalidNote)
+    );
+    for _ in 0..100 {
+        music.tick(&mut buzzer, 5);
+    }
+    assert_eq!(music.state(), PlayState::Stopped);
+
+    // bundled songs don't use the buffer
+    music.play(&SONG);
+    assert!(music.play_rtttl(b"Bad:d=4:x").is_err());
+    music.tick(&mut buzzer, 5);
+    assert!(music.is_playing());
+    assert_eq!(music.current_note(), Some((CN, 4)));
+}
+
+#[test]
+fn play_song_keeps_changed_tempo_for_same_song() {
+    let mut music = sequencer();
+    let mut buzzer = SimBuzzer::new();
+    music.play_song(0);
+    assert_eq!(music.timing_unit_ms, SONGS[0].timing_unit_ms);
+    assert_eq!(music.octave_adjust, SONGS[0].octave_adjust);
+
+    music.timing_unit_ms = 40;
+    music.octave_adjust = 1;
+    music.stop(&mut buzzer);
+    music.play_song(0);
+    assert_eq!(music.timing_unit_ms, 40);
+    assert_eq!(music.octave_adjust, 1);
+
+    // another song brings its own
+    music.play_song(1);
+    assert_eq!(music.timing_unit_ms, SONGS[1].timing_unit_ms);
+    assert_eq!(music.octave_adjust, SONGS[1].octave_adjust);
+
+    // and so does the same one after something else played
+    music.play(&SONG);
+    music.timing_unit_ms = 40;
+    music.play_song(1);
+    assert_eq!(music.timing_unit_ms, SONGS[1].timing_unit_ms);
+}
+
+#[test]
+#[cfg(feature = "music")]
+fn jukebox_tempo_brings_a_slow_ringtone_into_range() {
+    use nonik0_sim::{eeprom::Eeprom, input::InputEvent, modes::MODE_NAMES, Badge};
+
+    // slow enough that the timing unit saturates
+    let mut badge = Badge::new(Eeprom::new());
+    assert!(badge
+        .context
+        .music
+        .play_rtttl(b"Slow:d=4,o=5,b=5:c")
+        .is_ok());
+    assert_eq!(badge.context.music.timing_unit_ms, u8::MAX);
+
+    // the player page comes up with a song playing, the tempo page is next
+    let index = MODE_NAMES.iter().position(|n| *n == b" Jukebox").unwrap();
+    badge.context.to_mode(index);
+    badge.tick();
+    badge.tick_with_event(Some(InputEvent::RightHeld));
+    badge.tick_with_event(Some(InputEvent::RightReleased));
+    assert_eq!(badge.context.music.timing_unit_ms, 100);
+}
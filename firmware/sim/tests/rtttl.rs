@@ -0,0 +1,132 @@
+// Host tests for the RTTTL parser in ../rust/src/rtttl.rs
+
+use nonik0_sim::{
+    music::{n, pause, unpack, AN, BN, CN, CS, DN, EN, END, FN, GS, SONGS},
+    rtttl::{parse_rtttl, rtttl_len, rtttl_name, rtttl_song, Error, RtttlSong},
+};
+
+fn parse(text: &str) -> Result<Vec<u16>, Error> {
+    let mut notes = [0xAAAA; 64];
+    let info = parse_rtttl(text.as_bytes(), &mut notes)?;
+    assert_eq!(notes[info.len], END);
+    Ok(notes[..info.len].to_vec())
+}
+
+#[test]
+fn defaults_apply_to_bare_notes() {
+    let notes = parse("x:d=8,o=4,b=120:c,d,e").unwrap();
+    assert_eq!(notes, [n(CN, 4, 8), n(DN, 4, 8), n(EN, 4, 8)]);
+}
+
+#[test]
+fn spec_defaults_when_section_is_empty() {
+    // d=4, o=6, b=63
+    let mut notes = [0; 4];
+    let info = parse_rtttl(b"x::a", &mut notes).unwrap();
+    assert_eq!(&notes[..2], [n(AN, 6, 16), END]);
+    assert_eq!(info.timing_unit_ms, 59);
+}
+
+#[test]
+fn durations() {
+    let notes = parse("x:d=4,o=5,b=100:1c,2c,4c,8c,16c,32c").unwrap();
+    let timings: Vec<u8> = notes.iter().map(|&note| unpack(note).2).collect();
+    assert_eq!(timings, [64, 32, 16, 8, 4, 2]);
+}
+
+#[test]
+fn dotted_notes() {
+    // the dot can come before or after the octave
+    let notes = parse("x:d=4,o=5,b=100:8c.,8c6.,32c.,c.").unwrap();
+    let timings: Vec<u8> = notes.iter().map(|&note| unpack(note).2).collect();
+    assert_eq!(timings, [12, 12, 3, 24]);
+    assert_eq!(unpack(notes[1]).1, 6);
+}
+
+#[test]
+fn sharps_and_octaves() {
+    let notes = parse("x:d=4,o=5,b=100:c#,g#7,e#,b#,h").unwrap();
+    assert_eq!(
+        notes,
+        [
+            n(CS, 5, 16),
+            n(GS, 7, 16),
+            n(FN, 5, 16),
+            n(CN, 6, 16),
+            n(BN, 5, 16)
+        ]
+    );
+}
+
+#[test]
+fn pauses() {
+    let notes = parse("x:d=4,o=5,b=100:8p,p,2p.").unwrap();
+    assert_eq!(notes, [pause(8), pause(16), pause(48)]);
+}
+
+#[test]
+fn whitespace_and_case() {
+    let notes = parse("x: D = 8 , O = 5 , B = 100 :\n  C , 4E6 ,\tP ").unwrap();
+    assert_eq!(notes, [n(CN, 5, 8), n(EN, 6, 16), pause(8)]);
+}
+
+#[test]
+fn tempo_and_octave_adjust() {
+    let mut notes = [0; 4];
+    let info = parse_rtttl(b"x:d=4,o=5,b=160:c,c6", &mut notes).unwrap();
+    assert_eq!(info.len, 2);
+    // 1/64 of a whole note at 160 bpm
+    assert_eq!(info.timing_unit_ms, 23);
+    // highest note moved up to the top octave
+    assert_eq!(info.octave_adjust, 2);
+
+    let info = parse_rtttl(b"x:d=4,o=4,b=900:c", &mut notes).unwrap();
+    assert_eq!(info.timing_unit_ms, 4);
+    // never more than the bundled songs use
+    assert_eq!(info.octave_adjust, 3);
+}
+
+#[test]
+fn errors() {
+    assert_eq!(parse("no sections"), Err(Error::MissingSection));
+    assert_eq!(parse("x:d=4"), Err(Error::MissingSection));
+    assert_eq!(parse("x:q=4:c"), Err(Error::InvalidDefault));
+    assert_eq!(parse("x:d=4,b=0:c"), Err(Error::InvalidDefault));
+    assert_eq!(parse("x:d=4 o=5:c"), Err(Error::InvalidDefault));
+    assert_eq!(parse("x:d=3:c"), Err(Error::InvalidDuration));
+    assert_eq!(parse("x:o=9:c"), Err(Error::InvalidOctave));
+    assert_eq!(parse("x::c,x"), Err(Error::InvalidNote));
+    assert_eq!(parse("x::c d"), Err(Error::InvalidNote));
+    assert_eq!(parse("x::8"), Err(Error::InvalidNote));
+    assert_eq!(parse("x::64c"), Err(Error::InvalidDuration));
+    assert_eq!(parse("x::c9"), Err(Error::InvalidOctave));
+    assert_eq!(parse("x::b#8"), Err(Error::InvalidOctave));
+}
+
+#[test]
+fn buffer_full() {
+    let mut notes = [0; 3];
+    assert!(parse_rtttl(b"x::c,d", &mut notes).is_ok());
+    assert_eq!(parse_rtttl(b"x::c,d,e", &mut notes), Err(Error::BufferFull));
+    assert_eq!(parse_rtttl(b"x::", &mut []), Err(Error::BufferFull));
+}
+
+#[test]
+fn compile_time_conversion() {
+    const TEXT: &[u8] = b"Scale:d=8,o=5,b=120:c,d,e,f";
+    const SONG: RtttlSong<{ rtttl_len(TEXT) }> = rtttl_song(TEXT);
+    assert_eq!(
+        SONG.notes,
+        [n(CN, 5, 8), n(DN, 5, 8), n(EN, 5, 8), n(FN, 5, 8), END]
+    );
+    assert_eq!(SONG.info.timing_unit_ms, 31);
+    assert_eq!(rtttl_name(TEXT), b"Scale");
+}
+
+#[test]
+fn bundled_songs_end() {
+    for song in SONGS {
+        assert_eq!(song.notes.last(), Some(&END));
+        assert!(song.timing_unit_ms > 0);
+    }
+}
@@ -0,0 +1,273 @@
+// Host tests for the EEPROM layout in ../rust/src/saved_settings.rs: migrations, wear
+// rings and the contacts ring
+
+use nonik0_sim::{
+    eeprom::{Eeprom, EEPROM_SIZE},
+    saved_settings::{SavedSettings, Setting, MAX_CONTACTS, SETTINGS_VERSION},
+};
+
+// v0 settings from 0x01: brightness, current, tone, name, last mode, random page, sensor
+// page and I2C page
+const V0_SETTINGS: [u8; 15] = [
+    7, 2, 0, b'A', b'L', b'I', b'C', b'E', b' ', b' ', b' ', 4, 1, 2, 3,
+];
+
+fn v0_eeprom() -> Eeprom {
+    let mut data = [0xFF; EEPROM_SIZE];
+    data[1..1 + V0_SETTINGS.len()].copy_from_slice(&V0_SETTINGS);
+    Eeprom::with_data(data)
+}
+
+fn name(settings: &SavedSettings) -> [u8; 8] {
+    let mut name = [0; 8];
+    settings.read_setting(Setting::Name, &mut name);
+    name
+}
+
+fn temp_path(test: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("nonik0-{}-{}.bin", test, std::process::id()))
+}
+
+fn assert_defaults(settings: &SavedSettings) {
+    assert_eq!(
+        settings.read_setting_byte(Setting::Version),
+        SETTINGS_VERSION
+    );
+    assert_eq!(settings.read_setting_byte(Setting::Brightness), 12);
+    assert_eq!(&name(settings), b" NONIK0 ");
+    assert_eq!(settings.read_setting_byte(Setting::LastMode), 1);
+    assert_eq!(settings.read_setting_byte(Setting::I2CAddress), 0x13);
+}
+
+#[test]
+fn fresh_eeprom_gets_defaults() {
+    assert_defaults(&SavedSettings::new(Eeprom::new()));
+}
+
+// settings that were in v0 keep their values, the newer ones get defaults
+#[test]
+fn migrates_from_v0() {
+    let settings = SavedSettings::new(v0_eeprom());
+    assert_eq!(
+        settings.read_setting_byte(Setting::Version),
+        SETTINGS_VERSION
+    );
+    assert_eq!(settings.read_setting_byte(Setting::Brightness), 7);
+    assert_eq!(settings.read_setting_byte(Setting::Current), 2);
+    assert!(!settings.read_setting_bool(Setting::Tone));
+    assert_eq!(&name(&settings), b"ALICE   ");
+    assert_eq!(settings.read_setting_byte(Setting::LastMode), 4);
+    assert_eq!(settings.read_setting_byte(Setting::RandomPage), 1);
+    assert_eq!(settings.read_setting_byte(Setting::SensorPage), 2);
+    assert_eq!(settings.read_setting_byte(Setting::I2CPage), 3);
+    assert_eq!(settings.read_setting_byte(Setting::I2CSpeed), 0);
+    assert_eq!(settings.read_setting_byte(Setting::I2CRiseTime), 35);
+    assert_eq!(settings.read_setting_byte(Setting::I2CAddress), 0x13);
+    assert_eq!(settings.read_setting_byte(Setting::BatteryCell), 0);
+}
+
+#[test]
+fn bad_checksum_restores_defaults() {
+    let path = temp_path("checksum");
+    std::fs::write(&path, [0xFF; EEPROM_SIZE]).unwrap();
+    let mut settings = SavedSettings::new(Eeprom::open(&path).unwrap());
+    settings.save_setting_byte(Setting::Brightness, 9);
+
+    // a byte of the checksummed block changes behind the checksum's back
+    let mut data = std::fs::read(&path).unwrap();
+    data[Setting::Current as usize] ^= 0x04;
+    std::fs::write(&path, &data).unwrap();
+
+    let settings = SavedSettings::new(Eeprom::open(&path).unwrap());
+    std::fs::remove_file(&path).unwrap();
+    assert_defaults(&settings);
+}
+
+#[test]
+fn unknown_version_restores_defaults() {
+    let mut data = [0xFF; EEPROM_SIZE];
+    data[0] = SETTINGS_VERSION + 1;
+    data[2..2 + V0_SETTINGS.len()].copy_from_slice(&V0_SETTINGS);
+    assert_defaults(&SavedSettings::new(Eeprom::with_data(data)));
+}
+
+#[test]
+fn migrated_settings_stay_valid() {
+    // a file backed EEPROM, so the migrated contents can be opened again
+    let path = temp_path("migrated");
+    let mut data = [0xFF; EEPROM_SIZE];
+    data[1..1 + V0_SETTINGS.len()].copy_from_slice(&V0_SETTINGS);
+    std::fs::write(&path, data).unwrap();
+
+    let mut settings = SavedSettings::new(Eeprom::open(&path).unwrap());
+    settings.save_setting_byte(Setting::Brightness, 9);
+    settings.save_setting_byte(Setting::LastMode, 5);
+
+    // the checksum holds, nothing goes back to defaults
+    let settings = SavedSettings::new(Eeprom::open(&path).unwrap());
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(settings.read_setting_byte(Setting::Brightness), 9);
+    assert_eq!(settings.read_setting_byte(Setting::LastMode), 5);
+    assert_eq!(&name(&settings), b"ALICE   ");
+    assert_eq!(settings.read_setting_byte(Setting::I2CRiseTime), 35);
+}
+
+// status bytes per wear ring, followed by as many value bytes
+const RING_SLOTS: usize = 16;
+
+// slot holding the ring's current value: the last one before the status sequence breaks
+fn ring_slot(data: &[u8], setting: Setting) -> usize {
+    let status = &data[setting as usize..][..RING_SLOTS];
+    (1..RING_SLOTS)
+        .find(|&slot| status[slot] != status[slot - 1].wrapping_add(1))
+        .map_or(RING_SLOTS - 1, |slot| slot - 1)
+}
+
+fn ring_value(data: &[u8], setting: Setting, slot: usize) -> u8 {
+    data[setting as usize + RING_SLOTS + slot]
+}
+
+#[test]
+fn wear_ring_rotates_and_wraps() {
+    let path = temp_path("ring");
+    let mut settings = SavedSettings::new(Eeprom::open(&path).unwrap());
+    let data = std::fs::read(&path).unwrap();
+    let first_slot = ring_slot(&data, Setting::LastMode);
+    let random_page = settings.read_setting_byte(Setting::RandomPage);
+
+    // one more write than there are slots, each goes to the next slot
+    for i in 1..=RING_SLOTS + 1 {
+        let value = 10 + i as u8;
+        settings.save_setting_byte(Setting::LastMode, value);
+        assert_eq!(settings.read_setting_byte(Setting::LastMode), value);
+
+        let data = std::fs::read(&path).unwrap();
+        let slot = ring_slot(&data, Setting::LastMode);
+        assert_eq!(slot, (first_slot + i) % RING_SLOTS, "write {i}");
+        assert_eq!(ring_value(&data, Setting::LastMode, slot), value);
+    }
+
+    // the same value again doesn't use up a slot
+    let before = std::fs::read(&path).unwrap();
+    settings.save_setting_byte(Setting::LastMode, 10 + RING_SLOTS as u8 + 1);
+    assert_eq!(std::fs::read(&path).unwrap(), before);
+
+    // the other ring is untouched and both survive a restart
+    let settings = SavedSettings::new(Eeprom::open(&path).unwrap());
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(
+        settings.read_setting_byte(Setting::LastMode),
+        10 + RING_SLOTS as u8 + 1
+    );
+    assert_eq!(settings.read_setting_byte(Setting::RandomPage), random_page);
+}
+
+#[test]
+fn wear_ring_keeps_old_value_after_interrupted_write() {
+    let path = temp_path("ring-interrupted");
+    let mut settings = SavedSettings::new(Eeprom::open(&path).unwrap());
+    settings.save_setting_byte(Setting::RandomPage, 2);
+
+    // power lost after the value went into the next slot, before its status did
+    let mut data = std::fs::read(&path).unwrap();
+    let slot = ring_slot(&data, Setting::RandomPage);
+    let next_slot = (slot + 1) % RING_SLOTS;
+    data[Setting::RandomPage as usize + RING_SLOTS + next_slot] = 3;
+    std::fs::write(&path, &data).unwrap();
+
+    let mut settings = SavedSettings::new(Eeprom::open(&path).unwrap());
+    assert_eq!(settings.read_setting_byte(Setting::RandomPage), 2);
+
+    // the next write takes that slot over
+    settings.save_setting_byte(Setting::RandomPage, 4);
+    let data = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(ring_slot(&data, Setting::RandomPage), next_slot);
+    assert_eq!(settings.read_setting_byte(Setting::RandomPage), 4);
+}
+
+// the contacts ring follows the two wear rings, one 8 byte name per slot
+const CONTACTS_START: usize = Setting::RandomPage as usize + 2 * RING_SLOTS;
+const CONTACT_SLOTS: usize = MAX_CONTACTS + 1;
+
+fn contact_name(i: usize) -> [u8; 8] {
+    let mut name = *b"NAME    ";
+    name[5] = b'A' + i as u8;
+    name
+}
+
+fn contacts(settings: &SavedSettings) -> Vec<[u8; 8]> {
+    (0..MAX_CONTACTS)
+        .map_while(|index| {
+            let mut buf = [0; 8];
+            settings.read_contact(index, &mut buf).then_some(buf)
+        })
+        .collect()
+}
+
+#[test]
+fn contacts_newest_first_without_duplicates() {
+    let mut settings = SavedSettings::new(Eeprom::new());
+    assert!(contacts(&settings).is_empty());
+
+    settings.save_contact(b"ALICE   ");
+    settings.save_contact(b"BOB     ");
+    settings.save_contact(b"CAROL   ");
+    assert_eq!(
+        contacts(&settings),
+        [*b"CAROL   ", *b"BOB     ", *b"ALICE   "]
+    );
+
+    // linking with a known badge again keeps its place
+    settings.save_contact(b"ALICE   ");
+    assert_eq!(
+        contacts(&settings),
+        [*b"CAROL   ", *b"BOB     ", *b"ALICE   "]
+    );
+    assert!(!settings.read_contact(3, &mut [0; 8]));
+}
+
+#[test]
+fn contacts_drop_the_oldest_when_full() {
+    let mut settings = SavedSettings::new(Eeprom::new());
+    for i in 0..MAX_CONTACTS + 2 {
+        settings.save_contact(&contact_name(i));
+    }
+
+    let expected: Vec<_> = (2..MAX_CONTACTS + 2).rev().map(contact_name).collect();
+    assert_eq!(contacts(&settings), expected);
+
+    // restoring the default settings keeps them
+    settings.restore_defaults();
+    assert_eq!(contacts(&settings), expected);
+}
+
+#[test]
+fn contacts_recover_from_interrupted_write() {
+    // power lost after a name filled the last free slot, before the slot after it was
+    // erased: every slot holds a name
+    let path = temp_path("contacts");
+    std::fs::write(&path, [0xFF; EEPROM_SIZE]).unwrap();
+    let mut settings = SavedSettings::new(Eeprom::open(&path).unwrap());
+    for i in 0..MAX_CONTACTS {
+        settings.save_contact(&contact_name(i));
+    }
+    let mut data = std::fs::read(&path).unwrap();
+    let free = (0..CONTACT_SLOTS)
+        .find(|&slot| data[CONTACTS_START + slot * 8] == 0xFF)
+        .unwrap();
+    data[CONTACTS_START + free * 8..][..8].copy_from_slice(b"LOST    ");
+    std::fs::write(&path, &data).unwrap();
+
+    // the ring restarts at slot 0, the next name goes there and frees the slot after it
+    let mut settings = SavedSettings::new(Eeprom::open(&path).unwrap());
+    settings.save_contact(b"NEW     ");
+    let data = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(&data[CONTACTS_START..][..8], b"NEW     ");
+    assert_eq!(data[CONTACTS_START + 8], 0xFF);
+
+    let names = contacts(&settings);
+    assert_eq!(names.len(), MAX_CONTACTS);
+    assert_eq!(names[0], *b"NEW     ");
+}